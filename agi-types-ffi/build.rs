@@ -0,0 +1,18 @@
+use std::env;
+use std::path::Path;
+
+// Generates agi_types.h in OUT_DIR from the extern "C" functions in src/lib.rs.  The copy in include/ is only updated
+// on request, see tests/header.rs
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml")).expect("Unable to read cbindgen.toml");
+
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("Unable to generate the C header")
+        .write_to_file(Path::new(&out_dir).join("agi_types.h"));
+}
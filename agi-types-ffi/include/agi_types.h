@@ -0,0 +1,131 @@
+#ifndef AGI_TYPES_H
+#define AGI_TYPES_H
+
+/* Generated by cbindgen from src/lib.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define AGI_VIEWPORT_WIDTH 160
+
+#define AGI_VIEWPORT_HEIGHT 168
+
+/*
+ The size of the buffers agi_game_render fills, one AGI colour index per pixel, row by row
+ */
+#define AGI_VIEWPORT_PIXELS (AGI_VIEWPORT_WIDTH * AGI_VIEWPORT_HEIGHT)
+
+typedef enum AgiResourceType {
+  AGI_RESOURCE_TYPE_LOGIC,
+  AGI_RESOURCE_TYPE_PICTURE,
+  AGI_RESOURCE_TYPE_VIEW,
+  AGI_RESOURCE_TYPE_SOUND,
+  AGI_RESOURCE_TYPE_OTHER,
+} AgiResourceType;
+
+typedef enum AgiStatus {
+  AGI_STATUS_OK = 0,
+  AGI_STATUS_NULL_ARGUMENT,
+  AGI_STATUS_OUT_OF_RANGE,
+  AGI_STATUS_BUFFER_TOO_SMALL,
+  AGI_STATUS_FAILED,
+} AgiStatus;
+
+/*
+ An open game, from agi_game_open
+ */
+typedef struct AgiGame AgiGame;
+
+/*
+ Where a resource was read from
+ */
+typedef struct AgiResourceInfo {
+  enum AgiResourceType resource_type;
+  /*
+   The resource's number, its entry in the directory file
+   */
+  size_t resource_index;
+  uint8_t vol_file;
+  size_t vol_file_offset;
+  /*
+   The length of the data from agi_game_resource_data
+   */
+  size_t size;
+} AgiResourceInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ The message for the last failure on this thread, or null if nothing has failed.  The string is valid until the next
+ call that fails on this thread.
+ */
+const char *agi_last_error(void);
+
+/*
+ Opens the game installed in game_dir, a UTF-8 path, returning null on failure
+
+ # Safety
+ game_dir must be null or a null terminated string.
+ */
+struct AgiGame *agi_game_open(const char *game_dir);
+
+/*
+ Releases a game from agi_game_open, doing nothing if game is null
+
+ # Safety
+ game must be null or a handle from agi_game_open that hasn't been freed yet.
+ */
+void agi_game_free(struct AgiGame *game);
+
+/*
+ The number of pictures in the game, numbered from 0 in PICDIR order
+
+ # Safety
+ game must be null or a live handle from agi_game_open.
+ */
+size_t agi_game_pic_count(const struct AgiGame *game);
+
+/*
+ Fills info with where picture pic was read from
+
+ # Safety
+ game must be null or a live handle from agi_game_open, and info null or writable.
+ */
+enum AgiStatus agi_game_resource_info(const struct AgiGame *game,
+                                      size_t pic,
+                                      struct AgiResourceInfo *info);
+
+/*
+ The raw resource data of picture pic, without the VOL header, with its length written to size.  Returns null if
+ the picture doesn't exist.  The data belongs to the game and is valid until it's freed.
+
+ # Safety
+ game must be null or a live handle from agi_game_open, and size null or writable.
+ */
+const uint8_t *agi_game_resource_data(const struct AgiGame *game,
+                                      size_t pic,
+                                      size_t *size);
+
+/*
+ Renders picture pic into pic_buffer and pri_buffer, either of which can be null to skip it.  Each buffer must hold
+ buffer_size bytes, at least AGI_VIEWPORT_PIXELS, and receives one AGI colour index per pixel, row by row.
+
+ # Safety
+ game must be null or a live handle from agi_game_open, and the buffers null or writable for buffer_size bytes.
+ */
+enum AgiStatus agi_game_render(const struct AgiGame *game,
+                               size_t pic,
+                               uint8_t *pic_buffer,
+                               uint8_t *pri_buffer,
+                               size_t buffer_size);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* AGI_TYPES_H */
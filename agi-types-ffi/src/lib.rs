@@ -0,0 +1,259 @@
+//! A C API for opening AGI games and rendering their pictures, see include/agi_types.h for the generated header.
+//!
+//! Games are opaque handles from [`agi_game_open`] that must be released with [`agi_game_free`].  Functions that can
+//! fail return an [`AgiStatus`], and [`agi_last_error`] describes the most recent failure on the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use agi_types::{Game, AgiError, PixelBuffer, RenderOptions, render_to_buffers, PIC_BUFFER_BASE_COLOR, PRI_BUFFER_BASE_COLOR};
+
+// Written out rather than taken from agi_types so cbindgen can put them in the header
+pub const AGI_VIEWPORT_WIDTH : usize = 160;
+pub const AGI_VIEWPORT_HEIGHT : usize = 168;
+/// The size of the buffers agi_game_render fills, one AGI colour index per pixel, row by row
+pub const AGI_VIEWPORT_PIXELS : usize = AGI_VIEWPORT_WIDTH * AGI_VIEWPORT_HEIGHT;
+
+const _ : () = assert!(AGI_VIEWPORT_WIDTH == agi_types::VIEWPORT_WIDTH && AGI_VIEWPORT_HEIGHT == agi_types::VIEWPORT_HEIGHT);
+
+/// An open game, from agi_game_open
+pub struct AgiGame {
+    game : Game
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AgiStatus {
+    Ok = 0,
+    NullArgument,
+    OutOfRange,
+    BufferTooSmall,
+    Failed
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AgiResourceType {
+    Logic,
+    Picture,
+    View,
+    Sound,
+    Other
+}
+
+/// Where a resource was read from
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AgiResourceInfo {
+    pub resource_type : AgiResourceType,
+    /// The resource's number, its entry in the directory file
+    pub resource_index : usize,
+    pub vol_file : u8,
+    pub vol_file_offset : usize,
+    /// The length of the data from agi_game_resource_data
+    pub size : usize
+}
+
+thread_local! {
+    static LAST_ERROR : RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message : String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+fn error_message(error : &AgiError) -> String {
+    match error {
+        AgiError::Io(e) => format!("IO error: {}", e),
+        AgiError::Parse(message) => format!("Parse error: {}", message),
+        AgiError::Render(message) => format!("Render error: {}", message),
+        AgiError::Export(message) => format!("Export error: {}", message)
+    }
+}
+
+// Runs body, turning panics into AGI_STATUS_FAILED since they mustn't unwind into the caller
+fn guard(body : impl FnOnce() -> Result<(), (AgiStatus, String)>) -> AgiStatus {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => AgiStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        },
+        Err(_) => {
+            set_last_error(String::from("Internal error"));
+            AgiStatus::Failed
+        }
+    }
+}
+
+fn get_game<'a>(game : *const AgiGame) -> Result<&'a Game, (AgiStatus, String)> {
+    // SAFETY: the caller promises game is either null or a live handle from agi_game_open
+    unsafe { game.as_ref() }
+        .map(|game| &game.game)
+        .ok_or((AgiStatus::NullArgument, String::from("game is null")))
+}
+
+fn check_pic(game : &Game, pic : usize) -> Result<(), (AgiStatus, String)> {
+    if pic < game.pic_resources.len() {
+        Ok(())
+    } else {
+        Err((AgiStatus::OutOfRange, format!("There is no picture {}, the game only has {}", pic, game.pic_resources.len())))
+    }
+}
+
+/// The message for the last failure on this thread, or null if nothing has failed.  The string is valid until the next
+/// call that fails on this thread.
+#[no_mangle]
+pub extern "C" fn agi_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Opens the game installed in game_dir, a UTF-8 path, returning null on failure
+///
+/// # Safety
+/// game_dir must be null or a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_open(game_dir : *const c_char) -> *mut AgiGame {
+    let mut opened = ptr::null_mut();
+
+    guard(|| {
+        if game_dir.is_null() {
+            return Err((AgiStatus::NullArgument, String::from("game_dir is null")));
+        }
+
+        // SAFETY: checked for null above, and the caller promises it's null terminated
+        let game_dir = unsafe { CStr::from_ptr(game_dir) }.to_str()
+            .map_err(|_| (AgiStatus::Failed, String::from("game_dir isn't valid UTF-8")))?;
+
+        let game = Game::new_from_dir(Path::new(game_dir))
+            .map_err(|e| (AgiStatus::Failed, error_message(&e)))?;
+
+        opened = Box::into_raw(Box::new(AgiGame { game }));
+        Ok(())
+    });
+
+    opened
+}
+
+/// Releases a game from agi_game_open, doing nothing if game is null
+///
+/// # Safety
+/// game must be null or a handle from agi_game_open that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_free(game : *mut AgiGame) {
+    if !game.is_null() {
+        // SAFETY: the caller promises this came from Box::into_raw in agi_game_open and is only freed once
+        drop(unsafe { Box::from_raw(game) });
+    }
+}
+
+/// The number of pictures in the game, numbered from 0 in PICDIR order
+///
+/// # Safety
+/// game must be null or a live handle from agi_game_open.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_pic_count(game : *const AgiGame) -> usize {
+    get_game(game).map_or(0, |game| game.pic_resources.len())
+}
+
+/// Fills info with where picture pic was read from
+///
+/// # Safety
+/// game must be null or a live handle from agi_game_open, and info null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_resource_info(game : *const AgiGame, pic : usize, info : *mut AgiResourceInfo) -> AgiStatus {
+    guard(|| {
+        let game = get_game(game)?;
+        check_pic(game, pic)?;
+
+        if info.is_null() {
+            return Err((AgiStatus::NullArgument, String::from("info is null")));
+        }
+
+        let resource = &game.all_resources[pic];
+        let resource_type = match resource.get_resource_type() {
+            agi_types::AgiResourceType::Logic => AgiResourceType::Logic,
+            agi_types::AgiResourceType::Picture => AgiResourceType::Picture,
+            agi_types::AgiResourceType::View => AgiResourceType::View,
+            agi_types::AgiResourceType::Sound => AgiResourceType::Sound,
+            agi_types::AgiResourceType::Other => AgiResourceType::Other
+        };
+
+        // SAFETY: checked for null above, and the caller promises it's writable
+        unsafe {
+            info.write(AgiResourceInfo {
+                resource_type,
+                resource_index : resource.get_resource_index(),
+                vol_file : resource.get_vol_file(),
+                vol_file_offset : resource.get_vol_file_offset(),
+                size : resource.get_raw_data().len()
+            });
+        }
+
+        Ok(())
+    })
+}
+
+/// The raw resource data of picture pic, without the VOL header, with its length written to size.  Returns null if
+/// the picture doesn't exist.  The data belongs to the game and is valid until it's freed.
+///
+/// # Safety
+/// game must be null or a live handle from agi_game_open, and size null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_resource_data(game : *const AgiGame, pic : usize, size : *mut usize) -> *const u8 {
+    let mut data = ptr::null();
+
+    guard(|| {
+        let game = get_game(game)?;
+        check_pic(game, pic)?;
+
+        let raw_data = game.all_resources[pic].get_raw_data();
+        if !size.is_null() {
+            // SAFETY: checked for null above, and the caller promises it's writable
+            unsafe { size.write(raw_data.len()) };
+        }
+
+        data = raw_data.as_ptr();
+        Ok(())
+    });
+
+    data
+}
+
+/// Renders picture pic into pic_buffer and pri_buffer, either of which can be null to skip it.  Each buffer must hold
+/// buffer_size bytes, at least AGI_VIEWPORT_PIXELS, and receives one AGI colour index per pixel, row by row.
+///
+/// # Safety
+/// game must be null or a live handle from agi_game_open, and the buffers null or writable for buffer_size bytes.
+#[no_mangle]
+pub unsafe extern "C" fn agi_game_render(game : *const AgiGame, pic : usize, pic_buffer : *mut u8, pri_buffer : *mut u8, buffer_size : usize) -> AgiStatus {
+    guard(|| {
+        let game = get_game(game)?;
+        check_pic(game, pic)?;
+
+        if buffer_size < AGI_VIEWPORT_PIXELS {
+            return Err((AgiStatus::BufferTooSmall, format!("Buffers must hold {} bytes, got {}", AGI_VIEWPORT_PIXELS, buffer_size)));
+        }
+
+        let mut pic_pixels = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        let mut pri_pixels = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+        render_to_buffers(game.pic_resources[pic].get_instructions(), &RenderOptions::default(),
+            &mut (!pic_buffer.is_null()).then_some(&mut pic_pixels),
+            &mut (!pri_buffer.is_null()).then_some(&mut pri_pixels),
+            &mut None)
+            .map_err(|e| (AgiStatus::Failed, error_message(&e)))?;
+
+        for (buffer, pixels) in [(pic_buffer, &pic_pixels), (pri_buffer, &pri_pixels)] {
+            if !buffer.is_null() {
+                // SAFETY: checked for null above, and the caller promises it holds buffer_size bytes
+                unsafe { ptr::copy_nonoverlapping(pixels.get_pixel_indexes().as_ptr(), buffer, AGI_VIEWPORT_PIXELS) };
+            }
+        }
+
+        Ok(())
+    })
+}
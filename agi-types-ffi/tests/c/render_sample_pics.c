@@ -0,0 +1,65 @@
+/* Opens the game given as the first argument and renders every picture through the C API, printing a line per picture */
+#include <stdio.h>
+#include <string.h>
+
+#include "agi_types.h"
+
+static int fail(const char *what) {
+    const char *error = agi_last_error();
+    fprintf(stderr, "%s failed: %s\n", what, error ? error : "no error message");
+    return 1;
+}
+
+int main(int argc, char **argv) {
+    static uint8_t pic_buffer[AGI_VIEWPORT_PIXELS];
+    static uint8_t pri_buffer[AGI_VIEWPORT_PIXELS];
+
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s GAME_DIR\n", argv[0]);
+        return 2;
+    }
+
+    AgiGame *game = agi_game_open(argv[1]);
+    if (!game) {
+        return fail("agi_game_open");
+    }
+
+    size_t pic_count = agi_game_pic_count(game);
+    for (size_t pic = 0; pic < pic_count; pic++) {
+        AgiResourceInfo info;
+        if (agi_game_resource_info(game, pic, &info) != AGI_STATUS_OK) {
+            return fail("agi_game_resource_info");
+        }
+
+        size_t size = 0;
+        const uint8_t *data = agi_game_resource_data(game, pic, &size);
+        if (!data || size != info.size || data[size - 1] != 0xFF) {
+            return fail("agi_game_resource_data");
+        }
+
+        memset(pic_buffer, 0xAA, sizeof(pic_buffer));
+        memset(pri_buffer, 0xAA, sizeof(pri_buffer));
+        if (agi_game_render(game, pic, pic_buffer, pri_buffer, AGI_VIEWPORT_PIXELS) != AGI_STATUS_OK) {
+            return fail("agi_game_render");
+        }
+
+        unsigned long checksum = 0;
+        for (size_t i = 0; i < AGI_VIEWPORT_PIXELS; i++) {
+            if (pic_buffer[i] > 0x0F || pri_buffer[i] > 0x0F) {
+                fprintf(stderr, "picture %zu has an invalid colour at %zu\n", pic, i);
+                return 1;
+            }
+            checksum = checksum * 31 + pic_buffer[i] * 16 + pri_buffer[i];
+        }
+
+        printf("%zu %d %zu %zu %lu\n", pic, info.resource_type == AGI_RESOURCE_TYPE_PICTURE, info.resource_index, info.size, checksum);
+    }
+
+    if (agi_game_render(game, pic_count, pic_buffer, NULL, AGI_VIEWPORT_PIXELS) != AGI_STATUS_OUT_OF_RANGE) {
+        fprintf(stderr, "rendering past the last picture should fail\n");
+        return 1;
+    }
+
+    agi_game_free(game);
+    return 0;
+}
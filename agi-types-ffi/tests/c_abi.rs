@@ -0,0 +1,131 @@
+// Calls the library through its extern "C" declarations, the way a C caller would, rather than as Rust functions
+use std::env;
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::ptr;
+
+use agi_types::{Game, PixelBuffer, RenderOptions, render_to_buffers, PIC_BUFFER_BASE_COLOR, PRI_BUFFER_BASE_COLOR};
+use agi_types_ffi::{AgiResourceInfo, AgiResourceType, AgiStatus, AGI_VIEWPORT_PIXELS};
+
+// Opaque, as the header declares it
+#[repr(C)]
+struct AgiGame {
+    _private : [u8; 0]
+}
+
+extern "C" {
+    fn agi_last_error() -> *const c_char;
+    fn agi_game_open(game_dir : *const c_char) -> *mut AgiGame;
+    fn agi_game_free(game : *mut AgiGame);
+    fn agi_game_pic_count(game : *const AgiGame) -> usize;
+    fn agi_game_resource_info(game : *const AgiGame, pic : usize, info : *mut AgiResourceInfo) -> AgiStatus;
+    fn agi_game_resource_data(game : *const AgiGame, pic : usize, size : *mut usize) -> *const u8;
+    fn agi_game_render(game : *const AgiGame, pic : usize, pic_buffer : *mut u8, pri_buffer : *mut u8, buffer_size : usize) -> AgiStatus;
+}
+
+fn sample_pics_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("agi-types").join("sample_pics")
+}
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(agi_last_error()) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn sample_pics_match_the_rust_api() {
+    let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+    let game_dir = CString::new(sample_pics_dir().to_str().unwrap()).unwrap();
+
+    unsafe {
+        let handle = agi_game_open(game_dir.as_ptr());
+        assert!(!handle.is_null());
+        assert_eq!(agi_game_pic_count(handle), game.pic_resources.len());
+
+        let mut pic_buffer = vec![0u8; AGI_VIEWPORT_PIXELS];
+        let mut pri_buffer = vec![0u8; AGI_VIEWPORT_PIXELS];
+
+        for (pic, resource) in game.all_resources.iter().enumerate() {
+            let mut info = AgiResourceInfo { resource_type : AgiResourceType::Other, resource_index : 0, vol_file : 0, vol_file_offset : 0, size : 0 };
+            assert_eq!(agi_game_resource_info(handle, pic, &mut info), AgiStatus::Ok);
+            assert_eq!((info.resource_type, info.resource_index, info.vol_file_offset, info.size),
+                (AgiResourceType::Picture, resource.get_resource_index(), resource.get_vol_file_offset(), resource.get_raw_data().len()));
+
+            let mut size = 0usize;
+            let data = agi_game_resource_data(handle, pic, &mut size);
+            assert_eq!(std::slice::from_raw_parts(data, size), resource.get_raw_data().as_slice());
+
+            assert_eq!(agi_game_render(handle, pic, pic_buffer.as_mut_ptr(), pri_buffer.as_mut_ptr(), AGI_VIEWPORT_PIXELS), AgiStatus::Ok);
+
+            let mut expected_pic = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+            let mut expected_pri = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+            render_to_buffers(game.pic_resources[pic].get_instructions(), &RenderOptions::default(), &mut Some(&mut expected_pic), &mut Some(&mut expected_pri), &mut None).unwrap();
+            assert!(pic_buffer == expected_pic.get_pixel_indexes() && pri_buffer == expected_pri.get_pixel_indexes(), "PIC {} rendered differently", pic);
+        }
+
+        agi_game_free(handle);
+    }
+}
+
+#[test]
+fn bad_arguments_are_errors() {
+    let game_dir = CString::new(sample_pics_dir().to_str().unwrap()).unwrap();
+    let missing_dir = CString::new("/no/such/game").unwrap();
+
+    unsafe {
+        assert!(agi_game_open(ptr::null()).is_null());
+        assert!(agi_game_open(missing_dir.as_ptr()).is_null());
+        assert!(last_error().starts_with("IO error"));
+
+        assert_eq!(agi_game_pic_count(ptr::null()), 0);
+        assert_eq!(agi_game_render(ptr::null(), 0, ptr::null_mut(), ptr::null_mut(), AGI_VIEWPORT_PIXELS), AgiStatus::NullArgument);
+
+        let handle = agi_game_open(game_dir.as_ptr());
+        let pic_count = agi_game_pic_count(handle);
+        let mut buffer = vec![0u8; AGI_VIEWPORT_PIXELS];
+
+        assert_eq!(agi_game_render(handle, pic_count, buffer.as_mut_ptr(), ptr::null_mut(), buffer.len()), AgiStatus::OutOfRange);
+        assert_eq!(agi_game_render(handle, 0, buffer.as_mut_ptr(), ptr::null_mut(), buffer.len() - 1), AgiStatus::BufferTooSmall);
+        assert_eq!(agi_game_resource_info(handle, 0, ptr::null_mut()), AgiStatus::NullArgument);
+        assert!(agi_game_resource_data(handle, pic_count, ptr::null_mut()).is_null());
+        assert!(last_error().contains("There is no picture"));
+
+        agi_game_free(handle);
+        agi_game_free(ptr::null_mut());
+    }
+}
+
+// Builds tests/c/render_sample_pics.c against the header generated by the build and the shared library and runs it,
+// skipping if there's no C compiler
+#[cfg(unix)]
+#[test]
+fn c_program_renders_sample_pics() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // The test binary is in target/<profile>/deps, alongside the library cargo built for it
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("render_sample_pics");
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+
+    let compiled = Command::new(&compiler)
+        .arg(crate_dir.join("tests").join("c").join("render_sample_pics.c"))
+        .arg("-std=c99").arg("-Wall").arg("-Werror")
+        .arg("-I").arg(env!("OUT_DIR"))
+        .arg("-L").arg(&deps_dir).arg("-lagi_types_ffi")
+        .arg(format!("-Wl,-rpath,{}", deps_dir.display()))
+        .arg("-o").arg(&program)
+        .status();
+
+    match compiled {
+        Ok(status) => assert!(status.success(), "Compiling the C test failed"),
+        Err(_) => {
+            eprintln!("Skipping, no C compiler found ({})", compiler);
+            return;
+        }
+    }
+
+    let output = Command::new(&program).arg(sample_pics_dir()).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), game.pic_resources.len());
+}
@@ -0,0 +1,25 @@
+/*
+The build generates the C header in OUT_DIR, and include/agi_types.h is a checked in copy of it for C and C++ callers.
+After changing the C API, update the copy with:
+  cargo test -p agi-types-ffi --test header -- --ignored generate_header
+*/
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const GENERATED_HEADER : &str = include_str!(concat!(env!("OUT_DIR"), "/agi_types.h"));
+
+fn checked_in_header() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("include").join("agi_types.h")
+}
+
+#[test]
+fn checked_in_header_is_up_to_date() {
+    let checked_in = fs::read_to_string(checked_in_header()).unwrap();
+    assert!(checked_in == GENERATED_HEADER, "{} doesn't match the C API, regenerate it with the generate_header test", checked_in_header().display());
+}
+
+#[test]
+#[ignore]
+fn generate_header() {
+    fs::write(checked_in_header(), GENERATED_HEADER).unwrap();
+}
@@ -0,0 +1,159 @@
+/*
+Times rendering the sample pictures, both on their own and all of them together, with:
+  cargo bench -p agi-types --bench render
+
+Each picture is rendered twice, once by the library and once by a reference renderer that draws its lines the same
+way but fills with the queue based fill that the library used before its scanline fill, which tests and paints one
+pixel at a time and spreads to the 4 neighbours of each.  The two are checked to give the same pixels before they're
+timed, and the ratio of their times is the gain from the scanline fill.
+
+Each figure is the median of a number of timed passes after a few untimed ones, with the fastest and slowest passes
+alongside it to show how noisy the run was.  Pass a name to only run the benchmarks containing it, e.g.
+  cargo bench -p agi-types --bench render -- "all sample pics"
+*/
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use agi_types::*;
+use agi_types::pic_render::rasterize_line;
+
+const WARMUP_PASSES : usize = 3;
+const TIMED_PASSES : usize = 25;
+
+fn render(pic : &PicResource) -> (PixelBuffer, PixelBuffer) {
+    let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+    let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+    render_to_buffers(pic.get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut None).unwrap();
+    (pic_buffer, pri_buffer)
+}
+
+// The same fill rules as the library: the picture screen decides when picture drawing is on, otherwise the priority
+// screen does, and filling with the screen's own base colour fills nothing
+fn can_fill(x : usize, y : usize, pic_buffer : &PixelBuffer, pic_color : Option<u8>, pri_buffer : &PixelBuffer, pri_color : Option<u8>) -> bool {
+    match (pic_color, pri_color) {
+        (Some(pic_color), _) => pic_color != PIC_BUFFER_BASE_COLOR && pic_buffer.get_pixel_index(x, y).unwrap() == PIC_BUFFER_BASE_COLOR,
+        (None, Some(pri_color)) => pri_color != PRI_BUFFER_BASE_COLOR && pri_buffer.get_pixel_index(x, y).unwrap() == PRI_BUFFER_BASE_COLOR,
+        (None, None) => false
+    }
+}
+
+fn queue_fill(seed : PosU8, pic_buffer : &mut PixelBuffer, pic_color : Option<u8>, pri_buffer : &mut PixelBuffer, pri_color : Option<u8>, instruction_index : InstructionIndex) {
+    if seed.x as usize >= VIEWPORT_WIDTH || seed.y as usize >= VIEWPORT_HEIGHT {
+        return;
+    }
+
+    let mut queue = VecDeque::from([(seed.x as usize, seed.y as usize)]);
+    while let Some((x, y)) = queue.pop_front() {
+        if !can_fill(x, y, pic_buffer, pic_color, pri_buffer, pri_color) {
+            continue;
+        }
+
+        pic_buffer.set_pixel(x, y, pic_color, instruction_index).unwrap();
+        pri_buffer.set_pixel(x, y, pri_color, instruction_index).unwrap();
+
+        let neighbours = [
+            (y > 0).then(|| (x, y - 1)),
+            (x > 0).then(|| (x - 1, y)),
+            (x + 1 < VIEWPORT_WIDTH).then_some((x + 1, y)),
+            (y + 1 < VIEWPORT_HEIGHT).then_some((x, y + 1))
+        ];
+        for (x, y) in neighbours.into_iter().flatten() {
+            if can_fill(x, y, pic_buffer, pic_color, pri_buffer, pri_color) {
+                queue.push_back((x, y));
+            }
+        }
+    }
+}
+
+fn render_with_queue_fill(pic : &PicResource) -> (PixelBuffer, PixelBuffer) {
+    let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+    let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+    let (mut pic_color, mut pri_color) = (None, None);
+
+    for (instruction_index, instruction) in pic.get_instructions().iter().enumerate() {
+        match instruction {
+            DerivedPicRenderInstruction::SetColor(_, PictureBufferType::Picture, color) => pic_color = color.map(|color| color & 0x0F),
+            DerivedPicRenderInstruction::SetColor(_, PictureBufferType::Priority, color) => pri_color = color.map(|color| color & 0x0F),
+            DerivedPicRenderInstruction::DrawLines(_, points) => {
+                let points : Vec<PosU8> = points.iter().map(|point| point.clamped_to_viewport()).collect();
+                let lines = if points.len() == 1 { vec![[points[0], points[0]]] } else { points.windows(2).map(|line| [line[0], line[1]]).collect() };
+
+                for (sub_index, [start, end]) in lines.into_iter().enumerate() {
+                    rasterize_line(start, end, |x, y| {
+                        pic_buffer.set_pixel(x, y, pic_color, InstructionIndex::new_sub(instruction_index, sub_index))?;
+                        pri_buffer.set_pixel(x, y, pri_color, InstructionIndex::new_sub(instruction_index, sub_index))
+                    })
+                    .unwrap();
+                }
+            },
+            DerivedPicRenderInstruction::Fill(_, points) => {
+                for (sub_index, point) in points.iter().enumerate() {
+                    queue_fill(*point, &mut pic_buffer, pic_color, &mut pri_buffer, pri_color, InstructionIndex::new_sub(instruction_index, sub_index));
+                }
+            },
+            _ => ()
+        }
+    }
+
+    (pic_buffer, pri_buffer)
+}
+
+// The median time of the passes, or None if the name was filtered out
+fn bench(name : &str, filter : Option<&str>, mut pass : impl FnMut()) -> Option<Duration> {
+    if filter.is_some_and(|filter| !name.contains(filter)) {
+        return None;
+    }
+
+    for _ in 0..WARMUP_PASSES {
+        pass();
+    }
+
+    let mut times : Vec<Duration> = (0..TIMED_PASSES).map(|_| {
+        let start = Instant::now();
+        pass();
+        start.elapsed()
+    })
+    .collect();
+    times.sort();
+
+    println!("{:<36} median {:>10.2?}   min {:>10.2?}   max {:>10.2?}", name, times[TIMED_PASSES / 2], times[0], times[TIMED_PASSES - 1]);
+    Some(times[TIMED_PASSES / 2])
+}
+
+fn bench_against_queue_fill(name : &str, filter : Option<&str>, pics : &[PicResource]) {
+    let scanline = bench(name, filter, || {
+        for pic in pics {
+            black_box(render(pic));
+        }
+    });
+    let queue = bench(&format!("{} (queue fill)", name), filter, || {
+        for pic in pics {
+            black_box(render_with_queue_fill(pic));
+        }
+    });
+
+    if let (Some(scanline), Some(queue)) = (scanline, queue) {
+        println!("{:<36} {:.1}x faster with the scanline fill", name, queue.as_secs_f64() / scanline.as_secs_f64());
+    }
+}
+
+fn main() {
+    // cargo bench passes --bench, anything else is a name to filter on
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let game = Game::new_from_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")).unwrap();
+
+    // A faster renderer that draws something else wouldn't be a gain
+    for (i, pic) in game.pic_resources.iter().enumerate() {
+        let ((pic_buffer, pri_buffer), (queue_pic_buffer, queue_pri_buffer)) = (render(pic), render_with_queue_fill(pic));
+        assert!(pic_buffer.get_pixel_indexes() == queue_pic_buffer.get_pixel_indexes(), "Sample pic {} has a different picture screen with the queue fill", i);
+        assert!(pri_buffer.get_pixel_indexes() == queue_pri_buffer.get_pixel_indexes(), "Sample pic {} has a different priority screen with the queue fill", i);
+    }
+
+    bench_against_queue_fill("all sample pics", filter.as_deref(), &game.pic_resources);
+
+    for (i, pic) in game.pic_resources.iter().enumerate() {
+        bench_against_queue_fill(&format!("sample pic {:02}", i), filter.as_deref(), std::slice::from_ref(pic));
+    }
+}
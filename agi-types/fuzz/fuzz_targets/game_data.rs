@@ -0,0 +1,13 @@
+#![no_main]
+
+// The directory and volume layer: the first byte is the number of PICDIR entries, followed by the PICDIR data and then
+// a single VOL file
+use agi_types::Game;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data : &[u8]| {
+    if let Some((entries, rest)) = data.split_first() {
+        let (pic_data, volume_data) = rest.split_at((*entries as usize * 3).min(rest.len()));
+        Game::new_from_data("fuzz", pic_data, &[volume_data.to_vec()]);
+    }
+});
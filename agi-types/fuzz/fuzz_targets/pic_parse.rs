@@ -0,0 +1,14 @@
+#![no_main]
+
+// Parses PIC resource data, and checks anything that can be encoded again reads back as the same instructions
+use agi_types::PicResource;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data : &[u8]| {
+    if let Ok(pic) = PicResource::new(data) {
+        if let Ok(encoded) = pic.to_bytes() {
+            let decoded = PicResource::new(&encoded).expect("Encoded data should parse");
+            assert_eq!(decoded, pic);
+        }
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+// Renders PIC resource data with the vectors and fill outlines, the first byte picks the render options
+use agi_types::{PicResource, PixelBuffer, RenderOptions, ShapeBuffer, render_to_buffers, PIC_BUFFER_BASE_COLOR, PRI_BUFFER_BASE_COLOR};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data : &[u8]| {
+    if let Some((options, pic_data)) = data.split_first() {
+        if let Ok(pic) = PicResource::new(pic_data) {
+            let render_options = RenderOptions {
+                render_only_selected_instruction : options & 0x01 != 0,
+                show_fill_outlines : options & 0x02 != 0,
+                last_instruction_sub_items : (options & 0x04 != 0).then_some((options >> 4) as usize)
+            };
+
+            let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+            let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+            let mut shapes = ShapeBuffer::new();
+            // Anything that parses should render, including coordinates off the edge of the screen
+            render_to_buffers(pic.get_instructions(), &render_options, &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut Some(&mut shapes)).unwrap();
+        }
+    }
+});
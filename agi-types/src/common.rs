@@ -1,6 +1,8 @@
 use egui::{Pos2, pos2};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// The errors from reading, rendering and exporting pictures
 #[derive(Error, Debug)]
 pub enum AgiError {
     #[error("IO error")]
@@ -8,7 +10,9 @@ pub enum AgiError {
     #[error("Parse error")]
     Parse(String),
     #[error("Render error")]
-    Render(String)
+    Render(String),
+    #[error("Export error")]
+    Export(String)
 }
 
 pub const VIEWPORT_WIDTH : usize = 160;
@@ -18,7 +22,7 @@ pub const VIEWPORT_PIXELS : usize = VIEWPORT_WIDTH * VIEWPORT_HEIGHT;
 pub const PIC_BUFFER_BASE_COLOR : u8 = 0xF; // White
 pub const PRI_BUFFER_BASE_COLOR : u8 = 0x4; // Red
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PosU8 {
     pub x : u8,
     pub y : u8
@@ -29,7 +33,12 @@ impl PosU8 {
         Self { x, y }
     }
 
-    pub fn to_pos2(&self) -> Pos2 {
+    pub fn to_pos2(self) -> Pos2 {
         pos2(self.x as f32, self.y as f32)
     }
+
+    /// The nearest point on the screen, which is where the interpreter draws coordinates that are off the edge
+    pub fn clamped_to_viewport(self) -> Self {
+        Self::new(self.x.min(VIEWPORT_WIDTH as u8 - 1), self.y.min(VIEWPORT_HEIGHT as u8 - 1))
+    }
 }
\ No newline at end of file
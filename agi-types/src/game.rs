@@ -0,0 +1,175 @@
+use crate::*;
+use std::path::{Path, PathBuf};
+use std::fs;
+
+use super::pic::PicResource;
+
+// A directory entry's VOL number is 4 bits, and 0xF marks a missing resource
+const MAX_VOL_FILES : u8 = 15;
+
+/// The pictures of an AGI v2 game, read from its PICDIR and VOL files
+pub struct Game {
+    pub dir_name : String,
+    /// Every picture in the game, in PICDIR order.  Entries for missing resources are skipped, so these are numbered by
+    /// position rather than resource number
+    pub pic_resources : Vec<PicResource>,
+    /// The resources the pictures were parsed from, `pic_resources[i]` comes from `all_resources[i]`
+    pub all_resources : Vec<Resource>
+}
+
+impl Game {
+    /// Reads and parses every picture in the game installed in game_dir
+    pub fn new_from_dir(game_dir : &Path) -> Result<Self, AgiError> {
+        // Directory entries refer to the VOL files by number, so they're read in number order rather than name order,
+        // where VOL.10 would come before VOL.2.  Missing ones are left empty, and anything pointing into them fails.
+        let mut volume_data = vec![];
+        for vol_file in 0..MAX_VOL_FILES {
+            let path = Self::get_vol_path(game_dir, vol_file);
+            if path.is_file() {
+                volume_data.resize(vol_file as usize, vec![]);
+                volume_data.push(fs::read(path)?);
+            }
+        }
+
+        let pic_data = fs::read(game_dir.join("PICDIR"))?;
+
+        Ok(Self::new_from_data(&game_dir.to_string_lossy(), &pic_data, &volume_data))
+    }
+
+    /// Parses every picture from the contents of a game's PICDIR and VOL files, with the VOL files in number order.
+    /// Pictures that can't be read are reported and skipped.
+    pub fn new_from_data(dir_name : &str, pic_data : &[u8], volume_data : &[Vec<u8>]) -> Self {
+        let mut pic_resources : Vec<PicResource> = vec![];
+        let mut all_resources : Vec<Resource> = vec![];
+        for offset in (0..pic_data.len()).step_by(3) {
+            match Resource::new(AgiResourceType::Picture, pic_data, offset / 3, volume_data) {
+                Ok(Some(val)) => {
+                    match PicResource::new(val.get_raw_data()) {
+                        Ok(pic) => {
+                            pic_resources.push(pic);
+                            all_resources.push(val);
+                        },
+                        Err(err) => println!("Error parsing picture {} from {}: {:?}", offset / 3, dir_name, err)
+                    }
+                },
+                Ok(None) => (),
+                Err(err) => println!("Error parsing asset from {} at offset {}: {:?}", Path::new(dir_name).join("PICDIR").to_str().unwrap_or("unknown"), offset, err)
+            }
+        }
+
+        Self {
+            dir_name : dir_name.to_string(),
+            pic_resources,
+            all_resources
+        }
+    }
+
+    /// Writes picture pic_index back into the game's files in dir_name, after any edits to its instructions.  The
+    /// encoded picture goes where it was in its VOL file if it fits, otherwise it's added to the end and its PICDIR
+    /// entry pointed at it.  The first save keeps copies of the original files as PICDIR.bak and VOL.n.bak, which later
+    /// saves leave alone.
+    pub fn save_pic(&mut self, pic_index : usize) -> Result<(), AgiError> {
+        let (pic, resource) = self.pic_resources.get(pic_index).zip(self.all_resources.get(pic_index))
+            .ok_or_else(|| AgiError::Export(format!("There is no picture {}", pic_index)))?;
+
+        let game_dir = Path::new(&self.dir_name);
+        let pic_dir_path = game_dir.join("PICDIR");
+        let vol_path = Self::get_vol_path(game_dir, resource.get_vol_file());
+
+        let mut pic_dir_data = fs::read(&pic_dir_path)?;
+        let mut volume_data = fs::read(&vol_path)?;
+
+        // Only update the resource once the files are written, so it still matches them if that fails
+        let mut updated = resource.clone();
+        updated.rewrite(pic.to_bytes()?, &mut pic_dir_data, &mut volume_data)?;
+
+        Self::backup(&pic_dir_path)?;
+        Self::backup(&vol_path)?;
+
+        // The VOL file first, so the directory never points past its end
+        Self::replace_file(&vol_path, &volume_data)?;
+        Self::replace_file(&pic_dir_path, &pic_dir_data)?;
+
+        self.all_resources[pic_index] = updated;
+
+        Ok(())
+    }
+
+    fn get_vol_path(game_dir : &Path, vol_file : u8) -> PathBuf {
+        game_dir.join(format!("VOL.{}", vol_file))
+    }
+
+    // Writes the new contents alongside the file and renames them over it, so a failed write can't leave it half written
+    fn replace_file(path : &Path, data : &[u8]) -> Result<(), AgiError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+
+        fs::write(&temp_path, data)?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    fn backup(path : &Path) -> Result<(), AgiError> {
+        let mut backup_path = path.as_os_str().to_owned();
+        backup_path.push(".bak");
+
+        if !Path::new(&backup_path).exists() {
+            fs::copy(path, &backup_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_pictures_read_back() {
+        // Work on a copy, saving changes the files
+        let sample_pics_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics");
+        let game_dir = std::env::temp_dir().join(format!("agi-types-save-test-{}", std::process::id()));
+        fs::create_dir_all(&game_dir).unwrap();
+        for file in ["PICDIR", "VOL.0"] {
+            fs::copy(sample_pics_dir.join(file), game_dir.join(file)).unwrap();
+        }
+
+        let mut game = Game::new_from_dir(&game_dir).unwrap();
+        let original = Game::new_from_dir(&game_dir).unwrap();
+
+        // Drop the first line of picture 2 and save it twice, the backups should still be the originals, and as it got
+        // shorter it's written over the old copy each time
+        let original_vol_len = fs::metadata(game_dir.join("VOL.0")).unwrap().len();
+        let line = game.pic_resources[2].get_instructions().iter().position(|i| matches!(i, DerivedPicRenderInstruction::DrawLines(..))).unwrap();
+        let removed = game.pic_resources[2].get_instructions_mut().remove(line);
+        game.save_pic(2).unwrap();
+        game.save_pic(2).unwrap();
+        assert_eq!(fs::metadata(game_dir.join("VOL.0")).unwrap().len(), original_vol_len);
+
+        // Growing it again means a new copy at the end of the VOL file, but only the first time
+        game.pic_resources[2].get_instructions_mut().insert(line, removed.clone());
+        game.pic_resources[2].get_instructions_mut().insert(line, removed);
+        game.save_pic(2).unwrap();
+        let grown_vol_len = fs::metadata(game_dir.join("VOL.0")).unwrap().len();
+        assert!(grown_vol_len > original_vol_len);
+        game.save_pic(2).unwrap();
+        assert_eq!(fs::metadata(game_dir.join("VOL.0")).unwrap().len(), grown_vol_len);
+
+        let saved = Game::new_from_dir(&game_dir).unwrap();
+        assert_eq!(saved.pic_resources.len(), original.pic_resources.len());
+        for (pic_index, pic) in saved.pic_resources.iter().enumerate() {
+            let expected = if pic_index == 2 { &game.pic_resources[2] } else { &original.pic_resources[pic_index] };
+            assert_eq!(pic.get_instructions(), expected.get_instructions(), "Picture {} differs", pic_index);
+        }
+        assert_eq!(saved.all_resources[2].get_vol_file_offset(), game.all_resources[2].get_vol_file_offset());
+
+        for file in ["PICDIR", "VOL.0"] {
+            assert_eq!(fs::read(game_dir.join(format!("{}.bak", file))).unwrap(), fs::read(sample_pics_dir.join(file)).unwrap());
+            assert!(!game_dir.join(format!("{}.tmp", file)).exists());
+        }
+
+        fs::remove_dir_all(&game_dir).unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+//! Parsing and rendering for the PIC resources of Sierra On-line's AGI v2 games.
+//!
+//! Open a game's directory with [`Game::new_from_dir`], which reads the PICDIR and VOL files and parses every picture
+//! into a [`PicResource`], then draw its instructions with [`render_to_buffers`]:
+//!
+//! ```no_run
+//! use agi_types::*;
+//!
+//! let game = Game::new_from_dir(std::path::Path::new("/path/to/game/"))?;
+//! let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+//! render_to_buffers(game.pic_resources[0].get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut None, &mut None)?;
+//! # Ok::<(), AgiError>(())
+//! ```
+//!
+//! The other modules edit pictures, export them as images, animations and SVG, upscale them, and render whole games in
+//! parallel.
+
+pub mod common;
+pub mod pic;
+pub mod pic_render;
+pub mod pic_edit;
+pub mod pic_batch;
+pub mod pic_svg;
+pub mod pic_export;
+pub mod pic_upscale;
+pub mod resource;
+pub mod game;
+
+pub use common::{AgiError, PosU8, VIEWPORT_WIDTH, VIEWPORT_HEIGHT, VIEWPORT_PIXELS, PIC_BUFFER_BASE_COLOR, PRI_BUFFER_BASE_COLOR};
+pub use game::Game;
+pub use resource::{AgiResourceType, Resource};
+pub use pic::{PicResource, PicRenderInstruction, DerivedPicRenderInstruction, PictureBufferType};
+pub use pic_render::{render_to_buffers, render_in_steps, RenderOptions, PixelBuffer, InstructionIndex, ShapeBuffer, Palette};
+pub use pic_upscale::{Upscaler, UpscaledPixels, UpscaledImage};
+
+// The egui version the colour and vector types come from, so dependents don't have to match it themselves
+pub use egui;
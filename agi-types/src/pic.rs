@@ -0,0 +1,668 @@
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PictureBufferType {
+    Picture,
+    Priority
+}
+
+/*
+PicResource and its instructions serialize to the JSON schema documented in docs/pic-resource.schema.json, e.g.
+
+{
+  "instructions": [
+    { "instruction": "SetPicColorAndEnablePicDraw", "color": 4 },
+    { "instruction": "AbsLine", "points": [ { "x": 10, "y": 20 }, { "x": 30, "y": 20 } ] },
+    { "instruction": "EndInstruction" }
+  ]
+}
+
+The names are those of PicRenderInstruction, and the points are always absolute positions, whichever encoding the
+instruction uses in the PIC data.  to_bytes turns the instructions back into PIC data.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PicResource {
+    instructions : Vec<DerivedPicRenderInstruction>
+}
+
+impl PicResource {
+    /// Parses the raw data of a PIC resource into its instructions
+    pub fn new(raw_data : &[u8]) -> Result<Self, AgiError> {
+        // Read the instructions
+        let mut offset = 0usize;
+
+        let mut resource = PicResource { 
+            instructions: vec![],
+        };
+
+        while offset < raw_data.len() {
+            let (instruction, next_offset) = DerivedPicRenderInstruction::create_from_vec(raw_data, offset)?;
+            resource.instructions.push(instruction);
+            offset = next_offset;
+        }
+
+        Ok(resource)
+    }
+
+    /// A picture made of the given instructions, e.g. to encode them with to_bytes
+    pub fn from_instructions(instructions : Vec<DerivedPicRenderInstruction>) -> Self {
+        Self { instructions }
+    }
+
+    /// Encodes the instructions as PIC resource data, the inverse of new
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AgiError> {
+        let mut output = vec![];
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            instruction.encode(&mut output)
+                .map_err(|e| match e {
+                    AgiError::Export(message) => AgiError::Export(format!("Instruction {}: {}", index, message)),
+                    e => e
+                })?;
+        }
+
+        Ok(output)
+    }
+
+    pub fn get_instructions(&self) -> &Vec<DerivedPicRenderInstruction> {
+        &self.instructions
+    }
+
+    /// The instructions to edit, see pic_edit for keeping the edits encodable and undoable
+    pub fn get_instructions_mut(&mut self) -> &mut Vec<DerivedPicRenderInstruction> {
+        &mut self.instructions
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PicRenderInstruction {
+    SetPicColorAndEnablePicDraw,
+    DisablePicDraw,
+    SetPriColorAndEnablePriDraw,
+    DisablePriDraw,
+    DrawYCorner,
+    DrawXCorner,
+    AbsLine,
+    RelLine,
+    Fill,
+    SetPenSizeAndStyle,
+    PlotWithPen,
+    EndInstruction,
+    Unknown
+}
+
+impl PicRenderInstruction {
+    pub fn opcode(&self) -> Option<u8> {
+        match self {
+            Self::SetPicColorAndEnablePicDraw => Some(0xF0),
+            Self::DisablePicDraw => Some(0xF1),
+            Self::SetPriColorAndEnablePriDraw => Some(0xF2),
+            Self::DisablePriDraw => Some(0xF3),
+            Self::DrawYCorner => Some(0xF4),
+            Self::DrawXCorner => Some(0xF5),
+            Self::AbsLine => Some(0xF6),
+            Self::RelLine => Some(0xF7),
+            Self::Fill => Some(0xF8),
+            Self::SetPenSizeAndStyle => Some(0xF9),
+            Self::PlotWithPen => Some(0xFA),
+            Self::EndInstruction => Some(0xFF),
+            Self::Unknown => None
+        }
+    }
+}
+
+impl Display for PicRenderInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#?}", self)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum CornerLineStartDirection {
+    StartOnX,
+    StartOnY
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "InstructionJson", try_from = "InstructionJson")]
+pub enum DerivedPicRenderInstruction {
+    SetColor(PicRenderInstruction, PictureBufferType, Option<u8>),
+    DrawLines(PicRenderInstruction, Vec<PosU8>),
+    Fill(PicRenderInstruction, Vec<PosU8>),
+    Unimplemented(PicRenderInstruction)
+}
+
+impl Display for DerivedPicRenderInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::SetColor(inst, _, color) => {
+                match color {
+                    Some(color) => format!("{:?} ({})", inst, get_color_str(*color)),
+                    None => format!("{:?}", inst)
+                }
+            },
+            Self::DrawLines(inst, points) |
+            Self::Fill(inst, points) => {
+                let points_str = points.iter().map(|l| format!("({},{})", l.x, l.y)).collect::<Vec<String>>().join(", ");
+                format!("{:?} {}", inst, points_str)
+            },
+            Self::Unimplemented(inst) => {
+                format!("{:?}", inst)
+            }
+        };
+
+        f.write_str(str.as_str())
+    }
+}
+
+impl DerivedPicRenderInstruction {
+    /// The number of parts of the instruction that are drawn separately, the lines of a line instruction or the seed
+    /// points of a fill, and 1 for everything else
+    pub fn get_sub_item_count(&self) -> usize {
+        match self {
+            Self::DrawLines(_, points) => points.len().saturating_sub(1).max(1),
+            Self::Fill(_, points) => points.len().max(1),
+            _ => 1
+        }
+    }
+
+    pub fn get_instruction(&self) -> &PicRenderInstruction {
+        match self {
+            Self::SetColor(inst, _, _) |
+            Self::DrawLines(inst, _) |
+            Self::Fill(inst, _) |
+            Self::Unimplemented(inst) => inst
+        }
+    }
+
+    pub fn create_from_vec(raw_data : &[u8], offset : usize) -> Result<(DerivedPicRenderInstruction, usize), AgiError> {
+        let instruction = *raw_data.get(offset)
+            .ok_or_else(|| AgiError::Parse(format!("Instruction offset {} is past the end of the {} bytes of data", offset, raw_data.len())))?;
+
+        // Extract the arguments
+        let mut current_offset = offset + 1;
+        while current_offset < raw_data.len() && raw_data[current_offset] & 0xF0 != 0xF0 {
+            current_offset += 1;
+        }
+        
+        let arguments = &raw_data[offset+1..current_offset];
+
+        // The color instructions can't do anything sensible without their color
+        let color_argument = || arguments.first().copied()
+            .ok_or_else(|| AgiError::Parse(format!("Instruction {:#04x} at offset {} is missing its color", instruction, offset)));
+
+        let derived_instruction = match instruction {
+            0xF0 => Self::SetColor(PicRenderInstruction::SetPicColorAndEnablePicDraw, PictureBufferType::Picture, Some(color_argument()?)),
+            0xF1 => Self::SetColor(PicRenderInstruction::DisablePicDraw, PictureBufferType::Picture, None),
+            0xF2 => Self::SetColor(PicRenderInstruction::SetPriColorAndEnablePriDraw, PictureBufferType::Priority, Some(color_argument()?)),
+            0xF3 => Self::SetColor(PicRenderInstruction::DisablePriDraw, PictureBufferType::Priority, None),
+            0xF4 => Self::DrawLines(PicRenderInstruction::DrawYCorner, Self::generate_corner_lines(arguments, CornerLineStartDirection::StartOnY)),
+            0xF5 => Self::DrawLines(PicRenderInstruction::DrawXCorner, Self::generate_corner_lines(arguments, CornerLineStartDirection::StartOnX)),
+            0xF6 => Self::DrawLines(PicRenderInstruction::AbsLine, Self::generate_point_pairs(arguments)),
+            0xF7 => Self::DrawLines(PicRenderInstruction::RelLine, Self::generate_rel_lines(arguments)),
+            0xF8 => Self::Fill(PicRenderInstruction::Fill, Self::generate_point_pairs(arguments)),
+            0xF9 => Self::Unimplemented(PicRenderInstruction::SetPenSizeAndStyle),
+            0xFA => Self::Unimplemented(PicRenderInstruction::PlotWithPen),
+            0xFF => Self::Unimplemented(PicRenderInstruction::EndInstruction),
+            _ => Self::Unimplemented(PicRenderInstruction::Unknown)
+        };
+
+        Ok((derived_instruction, current_offset))
+    }
+
+    /// Appends the PIC data for this instruction, the inverse of create_from_vec
+    pub fn encode(&self, output : &mut Vec<u8>) -> Result<(), AgiError> {
+        let instruction = self.get_instruction();
+        let opcode = instruction.opcode()
+            .ok_or_else(|| AgiError::Export(format!("{} has no opcode", instruction)))?;
+
+        output.push(opcode);
+
+        match self {
+            Self::SetColor(_, _, Some(color)) => output.push(Self::check_argument(*color)?),
+            Self::SetColor(_, _, None) => {},
+            Self::DrawLines(PicRenderInstruction::DrawYCorner, points) => Self::encode_corner_lines(points, CornerLineStartDirection::StartOnY, output)?,
+            Self::DrawLines(PicRenderInstruction::DrawXCorner, points) => Self::encode_corner_lines(points, CornerLineStartDirection::StartOnX, output)?,
+            Self::DrawLines(PicRenderInstruction::RelLine, points) => Self::encode_rel_lines(points, output)?,
+            Self::DrawLines(_, points) |
+            Self::Fill(_, points) => {
+                for point in points {
+                    output.push(Self::check_argument(point.x)?);
+                    output.push(Self::check_argument(point.y)?);
+                }
+            },
+            Self::Unimplemented(PicRenderInstruction::EndInstruction) => {},
+            Self::Unimplemented(instruction) => {
+                // The parser doesn't keep the pen arguments, so these can't be written back faithfully
+                return Err(AgiError::Export(format!("{} can't be encoded", instruction)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_argument(argument : u8) -> Result<u8, AgiError> {
+        // Anything from 0xF0 up would be read back as the next instruction
+        if argument & 0xF0 == 0xF0 {
+            Err(AgiError::Export(format!("Argument {:#04x} is out of range", argument)))
+        } else {
+            Ok(argument)
+        }
+    }
+
+    fn encode_corner_lines(points : &[PosU8], start_dir : CornerLineStartDirection, output : &mut Vec<u8>) -> Result<(), AgiError> {
+        if let Some((first, rest)) = points.split_first() {
+            output.push(Self::check_argument(first.x)?);
+            output.push(Self::check_argument(first.y)?);
+
+            let mut previous = *first;
+            let mut direction_is_x = start_dir == CornerLineStartDirection::StartOnX;
+
+            for point in rest {
+                if direction_is_x && point.y == previous.y {
+                    output.push(Self::check_argument(point.x)?);
+                } else if !direction_is_x && point.x == previous.x {
+                    output.push(Self::check_argument(point.y)?);
+                } else {
+                    return Err(AgiError::Export(format!("Corner line from ({},{}) to ({},{}) isn't along the {} axis",
+                        previous.x, previous.y, point.x, point.y, if direction_is_x { "x" } else { "y" })));
+                }
+
+                previous = *point;
+                direction_is_x = !direction_is_x;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_rel_lines(points : &[PosU8], output : &mut Vec<u8>) -> Result<(), AgiError> {
+        if let Some((first, rest)) = points.split_first() {
+            output.push(Self::check_argument(first.x)?);
+            output.push(Self::check_argument(first.y)?);
+
+            let mut previous = *first;
+
+            for point in rest {
+                let disp_x = point.x as i16 - previous.x as i16;
+                let disp_y = point.y as i16 - previous.y as i16;
+
+                // -7 on x would set the top nibble to 0xF, so it can't be told apart from an instruction
+                if !(-6..=7).contains(&disp_x) || !(-7..=7).contains(&disp_y) {
+                    return Err(AgiError::Export(format!("Relative line from ({},{}) to ({},{}) is too long",
+                        previous.x, previous.y, point.x, point.y)));
+                }
+
+                let sign_x = if disp_x < 0 { 0x80u8 } else { 0 };
+                let sign_y = if disp_y < 0 { 0x08u8 } else { 0 };
+
+                output.push(sign_x | ((disp_x.unsigned_abs() as u8) << 4) | sign_y | disp_y.unsigned_abs() as u8);
+
+                previous = *point;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn generate_corner_lines(arguments : &[u8], start_dir : CornerLineStartDirection) -> Vec<PosU8> {
+        let mut result : Vec<PosU8> = vec![];
+        
+        if arguments.len() >= 2 {
+            result.push(PosU8::new(arguments[0], arguments[1]));
+            
+            let (mut x, mut y) = (arguments[0], arguments[1]);
+            let mut direction_is_x = start_dir == CornerLineStartDirection::StartOnX;
+    
+            for arg in arguments.iter().skip(2) {
+                if direction_is_x {
+                    x = *arg;
+                } else {
+                    y = *arg;
+                }
+    
+                result.push(PosU8::new(x, y));
+
+                direction_is_x = !direction_is_x;
+            }
+        } else {
+            // TODO: Log error
+        }
+
+        result
+
+    }
+
+    fn generate_point_pairs(arguments : &[u8]) -> Vec<PosU8> {
+        let mut result : Vec<PosU8> = vec![];
+        
+        if !arguments.is_empty() && arguments.len().is_multiple_of(2) {
+            result.reserve_exact(arguments.len() / 2);
+
+            for i in (0..arguments.len()).step_by(2) {
+                result.push(PosU8::new(arguments[i], arguments[i+1]))
+            }
+        } else {
+            // TODO: Error
+        }
+
+        result
+    }
+
+    fn generate_rel_lines(arguments : &[u8]) -> Vec<PosU8> {
+        let mut result : Vec<PosU8> = vec![];
+        
+        if arguments.len() >= 2 {
+            // Convert the relative arguments to absolute.  Like ScummVM's draw_LineShort, each step is clamped to the
+            // viewport before the next is taken from it, so a line that runs off the edge comes back from the edge
+            let mut previous = PosU8::new(arguments[0], arguments[1]).clamped_to_viewport();
+            result.push(previous);
+
+            for arg in arguments.iter().skip(2) {
+                let sign_x = if 0x80 & arg > 0 { -1i8 } else { 1i8 };
+                let sign_y = if 0x08 & arg > 0 { -1i8 } else { 1i8 };
+
+                let disp_x = sign_x * ((arg & 0x70) >> 4) as i8;
+                let disp_y = sign_y * (arg & 0x07) as i8;
+
+                let x = (previous.x as i16 + disp_x as i16).clamp(0, VIEWPORT_WIDTH as i16 - 1) as u8;
+                let y = (previous.y as i16 + disp_y as i16).clamp(0, VIEWPORT_HEIGHT as i16 - 1) as u8;
+                previous = PosU8::new(x, y);
+                result.push(previous);
+            }
+        } else {
+            // TODO: Error
+        }
+
+        result
+    }
+}
+
+// The flattened form DerivedPicRenderInstruction is (de)serialized through, the buffer type is implied by the instruction
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstructionJson {
+    instruction : PicRenderInstruction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color : Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    points : Option<Vec<PosU8>>
+}
+
+impl From<DerivedPicRenderInstruction> for InstructionJson {
+    fn from(derived : DerivedPicRenderInstruction) -> Self {
+        match derived {
+            DerivedPicRenderInstruction::SetColor(instruction, _, color) => Self { instruction, color, points: None },
+            DerivedPicRenderInstruction::DrawLines(instruction, points) |
+            DerivedPicRenderInstruction::Fill(instruction, points) => Self { instruction, color: None, points: Some(points) },
+            DerivedPicRenderInstruction::Unimplemented(instruction) => Self { instruction, color: None, points: None }
+        }
+    }
+}
+
+impl TryFrom<InstructionJson> for DerivedPicRenderInstruction {
+    type Error = String;
+
+    fn try_from(json : InstructionJson) -> Result<Self, Self::Error> {
+        use PicRenderInstruction::*;
+
+        let InstructionJson { instruction, color, points } = json;
+
+        match (&instruction, color, points) {
+            (SetPicColorAndEnablePicDraw, Some(color), None) => Ok(Self::SetColor(instruction, PictureBufferType::Picture, Some(color))),
+            (SetPriColorAndEnablePriDraw, Some(color), None) => Ok(Self::SetColor(instruction, PictureBufferType::Priority, Some(color))),
+            (DisablePicDraw, None, None) => Ok(Self::SetColor(instruction, PictureBufferType::Picture, None)),
+            (DisablePriDraw, None, None) => Ok(Self::SetColor(instruction, PictureBufferType::Priority, None)),
+            (DrawYCorner | DrawXCorner | AbsLine | RelLine, None, Some(points)) => Ok(Self::DrawLines(instruction, points)),
+            (Fill, None, Some(points)) => Ok(Self::Fill(instruction, points)),
+            (SetPenSizeAndStyle | PlotWithPen | EndInstruction | Unknown, None, None) => Ok(Self::Unimplemented(instruction)),
+            (SetPicColorAndEnablePicDraw | SetPriColorAndEnablePriDraw, _, _) => Err(format!("{} takes a color and no points", instruction)),
+            (DrawYCorner | DrawXCorner | AbsLine | RelLine | Fill, _, _) => Err(format!("{} takes points and no color", instruction)),
+            _ => Err(format!("{} takes no color or points", instruction))
+        }
+    }
+}
+
+pub fn get_color_str(agi_color : u8) -> &'static str {
+    // From here: https://moddingwiki.shikadi.net/wiki/EGA_Palette
+    match agi_color {
+        0x00 => "black",
+        0x01 => "blue",
+        0x02 => "green",
+        0x03 => "cyan",
+        0x04 => "red",
+        0x05 => "magenta",
+        0x06 => "brown",
+        0x07 => "light gray",
+        0x08 => "dark gray",
+        0x09 => "light blue",
+        0x0A => "light green",
+        0x0B => "light cyan",
+        0x0C => "light red",
+        0x0D => "light magenta",
+        0x0E => "yellow",
+        0x0F => "white",
+        _ => "INVALID"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    #[test]
+    fn sample_pics_encode_to_their_original_bytes() {
+        let game = Game::new_from_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")).unwrap();
+
+        for (i, (pic, resource)) in game.pic_resources.iter().zip(game.all_resources.iter()).enumerate() {
+            assert_eq!(&pic.to_bytes().unwrap(), resource.get_raw_data(), "PIC {} didn't encode back to its original data", i);
+
+            let json = serde_json::to_string(pic).unwrap();
+            assert_eq!(&serde_json::from_str::<PicResource>(&json).unwrap(), pic, "PIC {} didn't survive JSON", i);
+        }
+    }
+
+    #[test]
+    fn json_uses_the_documented_schema() {
+        let data = [0xF0, 0x04, 0xF3, 0xF5, 10, 20, 30, 40, 0xF7, 10, 20, 0xE1, 0xFF];
+        let pic = PicResource::new(&data).unwrap();
+
+        assert_eq!(serde_json::to_value(&pic).unwrap(), serde_json::json!({
+            "instructions": [
+                { "instruction": "SetPicColorAndEnablePicDraw", "color": 4 },
+                { "instruction": "DisablePriDraw" },
+                { "instruction": "DrawXCorner", "points": [ { "x": 10, "y": 20 }, { "x": 30, "y": 20 }, { "x": 30, "y": 40 } ] },
+                { "instruction": "RelLine", "points": [ { "x": 10, "y": 20 }, { "x": 4, "y": 21 } ] },
+                { "instruction": "EndInstruction" }
+            ]
+        }));
+
+        assert!(serde_json::from_str::<PicResource>(r#"{ "instructions": [ { "instruction": "Fill", "color": 4 } ] }"#).is_err());
+        assert!(serde_json::from_str::<PicResource>(r#"{ "instructions": [ { "instruction": "Wobble" } ] }"#).is_err());
+    }
+
+    #[test]
+    fn color_instructions_need_a_color() {
+        assert!(PicResource::new(&[0xF0]).is_err());
+        assert!(PicResource::new(&[0xF2, 0xF6, 1, 1]).is_err());
+        assert_eq!(PicResource::new(&[0xF8, 0xF4, 1]).unwrap().get_instructions().len(), 2);
+        assert!(PicResource::new(&[]).unwrap().get_instructions().is_empty());
+    }
+
+    #[test]
+    fn relative_lines_are_clamped_at_each_step() {
+        // Off the bottom left corner and back, then starting off the right edge
+        let pic = PicResource::new(&[0xF7, 2, 165, 0xC3, 0x4B, 0xF7, 200, 10, 0x90, 0xFF]).unwrap();
+
+        assert_eq!(pic.get_instructions()[0], DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine,
+            vec![PosU8::new(2, 165), PosU8::new(0, 167), PosU8::new(4, 164)]));
+        assert_eq!(pic.get_instructions()[1], DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine,
+            vec![PosU8::new(159, 10), PosU8::new(158, 10)]));
+    }
+
+    #[test]
+    fn unencodable_instructions_are_errors() {
+        let encode = |instruction : DerivedPicRenderInstruction| PicResource::from_instructions(vec![instruction]).to_bytes();
+
+        assert!(encode(DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::DrawYCorner, vec![PosU8::new(1, 1), PosU8::new(2, 2)])).is_err());
+        assert!(encode(DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine, vec![PosU8::new(10, 10), PosU8::new(3, 10)])).is_err());
+        assert!(encode(DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::AbsLine, vec![PosU8::new(0xF0, 10)])).is_err());
+        assert!(encode(DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::PlotWithPen)).is_err());
+
+        assert_eq!(encode(DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine, vec![PosU8::new(10, 10), PosU8::new(4, 17)])).unwrap(),
+            vec![0xF7, 10, 10, 0xE7]);
+    }
+
+    /*
+    Property tests over random instruction sequences that only use what the PIC format can represent, so every one of
+    them should survive encoding and render without errors.  Their coordinates go up to 0xEF, well past the edges of
+    the viewport, which the interpreter draws on the nearest edge
+    */
+    mod round_trip {
+        use proptest::prelude::*;
+        use super::*;
+
+        fn point() -> impl Strategy<Value = PosU8> {
+            (0..VIEWPORT_WIDTH as u8, 0..VIEWPORT_HEIGHT as u8).prop_map(|(x, y)| PosU8::new(x, y))
+        }
+
+        // Any coordinate an argument can hold, weighted towards the edges of the viewport and just past them
+        fn coordinate(size : usize) -> impl Strategy<Value = u8> {
+            let last = size as u8 - 1;
+            prop_oneof![
+                0..=0xEFu8,
+                prop::sample::select(vec![0, last, last + 1, 0xEF]),
+                last - 1..=last + 2
+            ]
+        }
+
+        fn any_point() -> impl Strategy<Value = PosU8> {
+            (coordinate(VIEWPORT_WIDTH), coordinate(VIEWPORT_HEIGHT)).prop_map(|(x, y)| PosU8::new(x, y))
+        }
+
+        fn corner_lines(start_dir : CornerLineStartDirection) -> impl Strategy<Value = Vec<PosU8>> {
+            (any_point(), prop::collection::vec(coordinate(VIEWPORT_HEIGHT), 0..12)).prop_map(move |(start, moves)| {
+                let mut points = vec![start];
+                let mut direction_is_x = start_dir == CornerLineStartDirection::StartOnX;
+
+                for coordinate in moves {
+                    let previous = points[points.len() - 1];
+                    points.push(if direction_is_x {
+                        PosU8::new(coordinate, previous.y)
+                    } else {
+                        PosU8::new(previous.x, coordinate)
+                    });
+                    direction_is_x = !direction_is_x;
+                }
+
+                points
+            })
+        }
+
+        // Steps that would leave the viewport are turned around, or dropped if they can't be
+        fn rel_lines() -> impl Strategy<Value = Vec<PosU8>> {
+            (point(), prop::collection::vec((-6..=7i16, -7..=7i16), 0..12)).prop_map(|(start, steps)| {
+                let mut points = vec![start];
+
+                for (disp_x, disp_y) in steps {
+                    let previous = points[points.len() - 1];
+                    let step = |position : u8, disp : i16, min_disp : i16, size : usize| {
+                        [disp, -disp, 0].into_iter()
+                            .find(|d| *d >= min_disp && (0..size as i16).contains(&(position as i16 + d)))
+                            .map_or(position, |d| (position as i16 + d) as u8)
+                    };
+                    points.push(PosU8::new(step(previous.x, disp_x, -6, VIEWPORT_WIDTH), step(previous.y, disp_y, -7, VIEWPORT_HEIGHT)));
+                }
+
+                points
+            })
+        }
+
+        fn instruction() -> impl Strategy<Value = DerivedPicRenderInstruction> {
+            use DerivedPicRenderInstruction as Derived;
+            use PicRenderInstruction as Inst;
+
+            prop_oneof![
+                (0..16u8).prop_map(|c| Derived::SetColor(Inst::SetPicColorAndEnablePicDraw, PictureBufferType::Picture, Some(c))),
+                Just(Derived::SetColor(Inst::DisablePicDraw, PictureBufferType::Picture, None)),
+                (0..16u8).prop_map(|c| Derived::SetColor(Inst::SetPriColorAndEnablePriDraw, PictureBufferType::Priority, Some(c))),
+                Just(Derived::SetColor(Inst::DisablePriDraw, PictureBufferType::Priority, None)),
+                corner_lines(CornerLineStartDirection::StartOnY).prop_map(|p| Derived::DrawLines(Inst::DrawYCorner, p)),
+                corner_lines(CornerLineStartDirection::StartOnX).prop_map(|p| Derived::DrawLines(Inst::DrawXCorner, p)),
+                prop::collection::vec(any_point(), 0..12).prop_map(|p| Derived::DrawLines(Inst::AbsLine, p)),
+                rel_lines().prop_map(|p| Derived::DrawLines(Inst::RelLine, p)),
+                prop::collection::vec(any_point(), 0..4).prop_map(|p| Derived::Fill(Inst::Fill, p))
+            ]
+        }
+
+        fn instructions() -> impl Strategy<Value = Vec<DerivedPicRenderInstruction>> {
+            (prop::collection::vec(instruction(), 0..40), any::<bool>()).prop_map(|(mut instructions, end)| {
+                if end {
+                    instructions.push(DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::EndInstruction));
+                }
+                instructions
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn instructions_survive_encoding(instructions in instructions()) {
+                let pic = PicResource::from_instructions(instructions);
+                let data = pic.to_bytes().unwrap();
+
+                prop_assert_eq!(PicResource::new(&data).unwrap(), pic);
+            }
+
+            #[test]
+            fn any_data_parses_or_fails_cleanly(data in prop::collection::vec(any::<u8>(), 0..256)) {
+                if let Ok(pic) = PicResource::new(&data) {
+                    if let Ok(encoded) = pic.to_bytes() {
+                        prop_assert_eq!(PicResource::new(&encoded).unwrap(), pic);
+                    }
+                }
+            }
+        }
+
+        proptest! {
+            // Rendering is much slower than encoding, so fewer cases
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn instructions_render(instructions in instructions(), show_fill_outlines in any::<bool>()) {
+                let pic = PicResource::new(&PicResource::from_instructions(instructions).to_bytes().unwrap()).unwrap();
+                let instructions = pic.get_instructions();
+
+                let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+                let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+                let mut shapes = ShapeBuffer::new();
+                let render_options = RenderOptions { show_fill_outlines, ..Default::default() };
+                render_to_buffers(instructions, &render_options, &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut Some(&mut shapes)).unwrap();
+
+                // Every pixel is a valid color, and was drawn by one of the instructions if by anything
+                for buffer in [&pic_buffer, &pri_buffer] {
+                    prop_assert!(buffer.get_pixel_indexes().iter().all(|c| *c < 16));
+
+                    for y in 0..VIEWPORT_HEIGHT {
+                        for x in 0..VIEWPORT_WIDTH {
+                            let Some(index) = buffer.get_pixel_instruction(x, y).unwrap() else { continue };
+                            prop_assert!(index.get_base_index() < instructions.len());
+
+                            // Line pixels are between the ends of their segment on both axes, once those are moved onto
+                            // the screen, so nothing past an edge has wrapped around to the other side
+                            if let DerivedPicRenderInstruction::DrawLines(_, points) = &instructions[index.get_base_index()] {
+                                let start = points[index.get_sub_index()].clamped_to_viewport();
+                                let end = points[(index.get_sub_index() + 1).min(points.len() - 1)].clamped_to_viewport();
+                                prop_assert!((start.x.min(end.x)..=start.x.max(end.x)).contains(&(x as u8)), "({},{}) is outside {:?}-{:?}", x, y, start, end);
+                                prop_assert!((start.y.min(end.y)..=start.y.max(end.y)).contains(&(y as u8)), "({},{}) is outside {:?}-{:?}", x, y, start, end);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}, mpsc::{self, Receiver}};
+use std::thread;
+use crate::*;
+
+// The result of rendering one picture of a batch, pic_index being its position in the list given to render_batch
+pub struct BatchRenderResult {
+    pub pic_index : usize,
+    pub result : Result<RenderedPic, AgiError>
+}
+
+pub struct RenderedPic {
+    pub pic_buffer : PixelBuffer,
+    pub pri_buffer : PixelBuffer
+}
+
+/*
+Renders every picture in pics across a pool of worker threads, sending each one back through the returned channel as
+soon as it is done, so callers can show results as they come in rather than waiting for the whole lot.  Results arrive
+in whatever order the workers finish them, and the channel is closed once every picture has been sent, so a caller that
+does want to wait can just iterate the receiver.
+
+A thread_count of None uses one thread per available core.
+*/
+pub fn render_batch(pics : Vec<PicResource>, thread_count : Option<usize>) -> Receiver<BatchRenderResult> {
+    let (sender, receiver) = mpsc::channel();
+
+    let thread_count = thread_count
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+        .clamp(1, pics.len().max(1));
+
+    let pics = Arc::new(pics);
+    let next_pic = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..thread_count {
+        let (pics, next_pic, sender) = (pics.clone(), next_pic.clone(), sender.clone());
+
+        thread::spawn(move || {
+            loop {
+                let pic_index = next_pic.fetch_add(1, Ordering::Relaxed);
+                if pic_index >= pics.len() {
+                    break;
+                }
+
+                let result = render_pic(&pics[pic_index]);
+                if sender.send(BatchRenderResult { pic_index, result }).is_err() {
+                    // Nobody is listening anymore, so don't bother with the rest
+                    break;
+                }
+            }
+        });
+    }
+
+    receiver
+}
+
+fn render_pic(pic : &PicResource) -> Result<RenderedPic, AgiError> {
+    let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+    let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+
+    render_to_buffers(pic.get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut None)?;
+
+    Ok(RenderedPic { pic_buffer, pri_buffer })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    #[test]
+    fn batch_renders_match_single_renders() {
+        let game = Game::new_from_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")).unwrap();
+
+        let mut seen = vec![false; game.pic_resources.len()];
+        for rendered in render_batch(game.pic_resources.clone(), Some(3)) {
+            assert!(!seen[rendered.pic_index], "PIC {} was rendered twice", rendered.pic_index);
+            seen[rendered.pic_index] = true;
+
+            let rendered_pic = rendered.result.unwrap();
+            let expected = render_pic(&game.pic_resources[rendered.pic_index]).unwrap();
+            assert_eq!(rendered_pic.pic_buffer.get_pixel_indexes(), expected.pic_buffer.get_pixel_indexes());
+            assert_eq!(rendered_pic.pri_buffer.get_pixel_indexes(), expected.pri_buffer.get_pixel_indexes());
+        }
+
+        assert!(seen.iter().all(|s| *s), "Not every PIC was rendered");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::*;
+
+/*
+Building blocks for editing the instructions of a picture: where new instructions can go, the points each kind of line
+will accept, moving the points of an existing instruction without breaking its encoding, and an undo history.
+
+Corner lines alternate between horizontal and vertical segments, starting on the axis in their name, and relative
+lines can only step a few pixels at a time, so the points given to them are pulled back to the nearest one they can
+encode rather than rejected.
+*/
+
+// Each level is a copy of the instructions, which are only a few KB even for a busy picture
+pub const MAX_UNDO_LEVELS : usize = 200;
+
+// The furthest a relative line can step, -7 on x would read back as an instruction
+const REL_LINE_X_STEPS : (i16, i16) = (-6, 7);
+const REL_LINE_Y_STEPS : (i16, i16) = (-7, 7);
+
+/// The states of a picture before each edit, to undo and redo them
+#[derive(Default)]
+pub struct EditHistory {
+    undo : Vec<Vec<DerivedPicRenderInstruction>>,
+    redo : Vec<Vec<DerivedPicRenderInstruction>>
+}
+
+impl EditHistory {
+    /// Records the picture as it is before an edit, dropping anything that was undone
+    pub fn checkpoint(&mut self, pic : &PicResource) {
+        self.checkpoint_instructions(pic.get_instructions().clone());
+    }
+
+    /// Records instructions from before an edit that has already been made, for edits such as dragging a point that
+    /// are only worth undoing if they turn out to have changed something
+    pub fn checkpoint_instructions(&mut self, instructions : Vec<DerivedPicRenderInstruction>) {
+        if self.undo.len() == MAX_UNDO_LEVELS {
+            self.undo.remove(0);
+        }
+        self.undo.push(instructions);
+        self.redo.clear();
+    }
+
+    /// Puts the picture back to how it was before the last edit, returning false if there's nothing to undo
+    pub fn undo(&mut self, pic : &mut PicResource) -> bool {
+        match self.undo.pop() {
+            Some(instructions) => {
+                self.redo.push(std::mem::replace(pic.get_instructions_mut(), instructions));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Reapplies the last undone edit, returning false if there's nothing to redo
+    pub fn redo(&mut self, pic : &mut PicResource) -> bool {
+        match self.redo.pop() {
+            Some(instructions) => {
+                self.undo.push(std::mem::replace(pic.get_instructions_mut(), instructions));
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+/// Where to insert an instruction so it's drawn straight after instruction `after`, but never after the end marker,
+/// since the interpreter stops reading there
+pub fn get_insert_index(instructions : &[DerivedPicRenderInstruction], after : usize) -> usize {
+    let end = instructions.iter()
+        .position(is_end_marker)
+        .unwrap_or(instructions.len());
+
+    (after + 1).min(end)
+}
+
+/// Whether instruction `index` can be deleted, which is anything but the end marker, as long as another instruction is
+/// left to select
+pub fn can_delete_instruction(instructions : &[DerivedPicRenderInstruction], index : usize) -> bool {
+    instructions.len() > 1 && instructions.get(index).is_some_and(|instruction| !is_end_marker(instruction))
+}
+
+/// The instruction that instruction `index` swaps places with to move it up or down, or None if it can't move that way.
+/// The end marker never moves and nothing swaps with it, so no instruction ends up on the other side of it
+pub fn get_move_index(instructions : &[DerivedPicRenderInstruction], index : usize, up : bool) -> Option<usize> {
+    let other = if up { index.checked_sub(1)? } else { index + 1 };
+    let movable = |index : usize| instructions.get(index).is_some_and(|instruction| !is_end_marker(instruction));
+
+    (movable(index) && movable(other)).then_some(other)
+}
+
+fn is_end_marker(instruction : &DerivedPicRenderInstruction) -> bool {
+    *instruction.get_instruction() == PicRenderInstruction::EndInstruction
+}
+
+/// The closest point to `target` that can follow `points` in a line of the given kind
+pub fn constrain_next_point(kind : PicRenderInstruction, points : &[PosU8], target : PosU8) -> PosU8 {
+    let target = target.clamped_to_viewport();
+    let Some(previous) = points.last() else {
+        return target;
+    };
+
+    match kind {
+        PicRenderInstruction::DrawXCorner | PicRenderInstruction::DrawYCorner => {
+            if is_x_segment(kind, points.len() - 1) {
+                PosU8::new(target.x, previous.y)
+            } else {
+                PosU8::new(previous.x, target.y)
+            }
+        },
+        PicRenderInstruction::RelLine => {
+            let x = clamp_step(target.x, previous.x, REL_LINE_X_STEPS);
+            let y = clamp_step(target.y, previous.y, REL_LINE_Y_STEPS);
+            PosU8::new(x as u8, y as u8)
+        },
+        _ => target
+    }
+}
+
+/// Moves point `index` of a line or fill to `target`, taking its neighbours along where a corner line needs them to
+/// stay lined up, and stopping short where a relative line's steps would get too long
+pub fn move_point(instruction : &mut DerivedPicRenderInstruction, index : usize, target : PosU8) {
+    let kind = *instruction.get_instruction();
+    let points = match instruction {
+        DerivedPicRenderInstruction::DrawLines(_, points) |
+        DerivedPicRenderInstruction::Fill(_, points) => points,
+        _ => return
+    };
+
+    if index >= points.len() {
+        return;
+    }
+
+    let target = target.clamped_to_viewport();
+    match kind {
+        PicRenderInstruction::DrawXCorner | PicRenderInstruction::DrawYCorner => {
+            points[index] = target;
+
+            // A segment along x keeps both its ends at the same y, and one along y keeps the same x
+            if index > 0 {
+                if is_x_segment(kind, index - 1) { points[index - 1].y = target.y } else { points[index - 1].x = target.x }
+            }
+            if index + 1 < points.len() {
+                if is_x_segment(kind, index) { points[index + 1].y = target.y } else { points[index + 1].x = target.x }
+            }
+        },
+        PicRenderInstruction::RelLine => {
+            // Both the step in from the previous point and the step out to the next one have to stay short
+            let (mut x, mut y) = (target.x as i16, target.y as i16);
+            if index > 0 {
+                x = clamp_step(x as u8, points[index - 1].x, REL_LINE_X_STEPS);
+                y = clamp_step(y as u8, points[index - 1].y, REL_LINE_Y_STEPS);
+            }
+            if let Some(next) = points.get(index + 1) {
+                x = x.clamp(next.x as i16 - REL_LINE_X_STEPS.1, next.x as i16 - REL_LINE_X_STEPS.0);
+                y = y.clamp(next.y as i16 - REL_LINE_Y_STEPS.1, next.y as i16 - REL_LINE_Y_STEPS.0);
+            }
+            points[index] = PosU8::new(x as u8, y as u8);
+        },
+        _ => points[index] = target
+    }
+}
+
+// Corner lines start along the axis in their name and then alternate
+fn is_x_segment(kind : PicRenderInstruction, segment : usize) -> bool {
+    (kind == PicRenderInstruction::DrawXCorner) == segment.is_multiple_of(2)
+}
+
+// The nearest value to target that's within steps of from, from being on the screen keeps the result on it too
+fn clamp_step(target : u8, from : u8, steps : (i16, i16)) -> i16 {
+    (target as i16).clamp(from as i16 + steps.0, from as i16 + steps.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encodes(instruction : &DerivedPicRenderInstruction) -> bool {
+        instruction.encode(&mut vec![]).is_ok()
+    }
+
+    #[test]
+    fn next_points_can_be_encoded() {
+        let mut corner_points = vec![PosU8::new(10, 10)];
+        let mut rel_points = vec![PosU8::new(10, 10)];
+
+        for target in [PosU8::new(50, 30), PosU8::new(0, 0), PosU8::new(200, 200), PosU8::new(12, 9)] {
+            corner_points.push(constrain_next_point(PicRenderInstruction::DrawXCorner, &corner_points, target));
+            rel_points.push(constrain_next_point(PicRenderInstruction::RelLine, &rel_points, target));
+        }
+
+        assert_eq!(corner_points, [PosU8::new(10, 10), PosU8::new(50, 10), PosU8::new(50, 0), PosU8::new(159, 0), PosU8::new(159, 9)]);
+        assert_eq!(rel_points, [PosU8::new(10, 10), PosU8::new(17, 17), PosU8::new(11, 10), PosU8::new(18, 17), PosU8::new(12, 10)]);
+
+        assert!(encodes(&DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::DrawXCorner, corner_points)));
+        assert!(encodes(&DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine, rel_points)));
+    }
+
+    #[test]
+    fn moved_points_can_be_encoded() {
+        let mut corner = DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::DrawYCorner,
+            vec![PosU8::new(10, 10), PosU8::new(10, 20), PosU8::new(30, 20), PosU8::new(30, 40)]);
+        move_point(&mut corner, 1, PosU8::new(15, 25));
+        assert_eq!(corner, DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::DrawYCorner,
+            vec![PosU8::new(15, 10), PosU8::new(15, 25), PosU8::new(30, 25), PosU8::new(30, 40)]));
+        assert!(encodes(&corner));
+
+        let mut rel = DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine,
+            vec![PosU8::new(10, 10), PosU8::new(15, 15), PosU8::new(20, 20)]);
+        move_point(&mut rel, 1, PosU8::new(100, 0));
+        assert_eq!(rel, DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::RelLine,
+            vec![PosU8::new(10, 10), PosU8::new(17, 13), PosU8::new(20, 20)]));
+        assert!(encodes(&rel));
+    }
+
+    #[test]
+    fn end_marker_stays_at_the_end() {
+        let line = DerivedPicRenderInstruction::DrawLines(PicRenderInstruction::AbsLine, vec![PosU8::new(10, 10)]);
+        let end = DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::EndInstruction);
+        let instructions = [line.clone(), line.clone(), end];
+
+        assert_eq!(get_move_index(&instructions, 1, true), Some(0));
+        assert_eq!(get_move_index(&instructions, 0, false), Some(1));
+        assert_eq!(get_move_index(&instructions, 0, true), None);
+        assert_eq!(get_move_index(&instructions, 1, false), None);
+        assert_eq!(get_move_index(&instructions, 2, true), None);
+        assert_eq!(get_move_index(&instructions, 2, false), None);
+
+        assert!(can_delete_instruction(&instructions, 0));
+        assert!(!can_delete_instruction(&instructions, 2));
+        assert!(!can_delete_instruction(&instructions, 3));
+        assert!(!can_delete_instruction(&[line], 0));
+    }
+
+    #[test]
+    fn edits_can_be_undone_and_redone() {
+        let mut pic = PicResource::new(&[0xF0, 0x01, 0xF6, 10, 10, 20, 20, 0xFF]).unwrap();
+        let original = pic.get_instructions().clone();
+        let mut history = EditHistory::default();
+
+        history.checkpoint(&pic);
+        let index = get_insert_index(pic.get_instructions(), 2);
+        assert_eq!(index, 2, "New instructions go before the end marker");
+        pic.get_instructions_mut().insert(index, DerivedPicRenderInstruction::Fill(PicRenderInstruction::Fill, vec![PosU8::new(0, 0)]));
+        let edited = pic.get_instructions().clone();
+
+        assert!(history.undo(&mut pic));
+        assert_eq!(pic.get_instructions(), &original);
+        assert!(!history.undo(&mut pic));
+
+        assert!(history.redo(&mut pic));
+        assert_eq!(pic.get_instructions(), &edited);
+        assert!(!history.can_redo());
+
+        // An edit that's already been made can be recorded afterwards, from a copy of the instructions before it
+        history.checkpoint_instructions(original.clone());
+        assert!(history.undo(&mut pic));
+        assert_eq!(pic.get_instructions(), &original);
+    }
+}
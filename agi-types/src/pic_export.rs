@@ -0,0 +1,413 @@
+use byteorder::*;
+use egui::Color32;
+use crate::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Bmp
+}
+
+impl ImageFormat {
+    pub const ALL : [ImageFormat ; 3] = [ImageFormat::Png, ImageFormat::Ppm, ImageFormat::Bmp];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Ppm => "ppm",
+            Self::Bmp => "bmp"
+        }
+    }
+
+    pub fn from_extension(extension : &str) -> Option<ImageFormat> {
+        Self::ALL.into_iter().find(|f| f.extension().eq_ignore_ascii_case(extension))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageExportOptions {
+    pub format : ImageFormat,
+    pub upscaler : Upscaler, // Applied to the buffer first, before the stretch and scale
+    pub stretch : bool, // Doubles the width, as the pixels were shown on the original hardware (320x168)
+    pub scale : usize, // Integer upscale applied on top of the stretch
+    pub palette : Palette
+}
+
+impl Default for ImageExportOptions {
+    fn default() -> Self {
+        Self { format : ImageFormat::Png, upscaler : Upscaler::None, stretch : false, scale : 1, palette : Palette::EGA }
+    }
+}
+
+// A buffer at its export size, row by row from the top
+pub struct ScaledImage {
+    pub width : usize,
+    pub height : usize,
+    pub pixels : UpscaledPixels
+}
+
+impl ScaledImage {
+    pub fn new(buffer : &PixelBuffer, options : &ImageExportOptions) -> Result<Self, AgiError> {
+        if options.scale == 0 {
+            return Err(AgiError::Export(String::from("Scale must be at least 1")));
+        }
+
+        let upscaled = options.upscaler.upscale(buffer.get_pixel_indexes(), VIEWPORT_WIDTH, VIEWPORT_HEIGHT, &options.palette);
+
+        let x_scale = options.scale * if options.stretch { 2 } else { 1 };
+        let y_scale = options.scale;
+        let (width, height) = (upscaled.width * x_scale, upscaled.height * y_scale);
+
+        let pixels = match upscaled.pixels {
+            UpscaledPixels::Indexed(pixels) => UpscaledPixels::Indexed(scale_nearest(&pixels, upscaled.width, x_scale, y_scale)),
+            UpscaledPixels::Rgb(pixels) => UpscaledPixels::Rgb(scale_nearest(&pixels, upscaled.width, x_scale, y_scale))
+        };
+
+        Ok(Self { width, height, pixels })
+    }
+
+    pub fn to_rgb_bytes(&self, palette : &Palette) -> Vec<u8> {
+        let to_bytes = |c : &Color32| [c.r(), c.g(), c.b()];
+        match &self.pixels {
+            UpscaledPixels::Indexed(pixels) => pixels.iter().flat_map(|c| to_bytes(&palette.get_color(*c))).collect(),
+            UpscaledPixels::Rgb(pixels) => pixels.iter().flat_map(to_bytes).collect()
+        }
+    }
+}
+
+fn scale_nearest<T : Copy>(pixels : &[T], width : usize, x_scale : usize, y_scale : usize) -> Vec<T> {
+    let mut scaled = Vec::with_capacity(pixels.len() * x_scale * y_scale);
+    for row in pixels.chunks_exact(width) {
+        for _ in 0..y_scale {
+            scaled.extend(row.iter().flat_map(|p| std::iter::repeat_n(*p, x_scale)));
+        }
+    }
+    scaled
+}
+
+fn palette_bytes(palette : &Palette) -> Vec<u8> {
+    palette.colors.iter().flat_map(|c| [c.r(), c.g(), c.b()]).collect()
+}
+
+/*
+Encodes the contents of a picture or priority buffer as an image file.  PNG and BMP are written as 4-bit indexed
+images with the palette embedded, so the AGI color of every pixel survives the round trip, and PPM (binary P6) as
+plain RGB for tools that don't read anything fancier.  The upscalers that blend colors need more than the 16 in the
+palette, so with those PNGs are written as RGB instead, and BMP isn't available.
+*/
+pub fn export_buffer(buffer : &PixelBuffer, options : &ImageExportOptions) -> Result<Vec<u8>, AgiError> {
+    let image = ScaledImage::new(buffer, options)?;
+
+    match options.format {
+        ImageFormat::Png => encode_png(&image, &options.palette),
+        ImageFormat::Ppm => Ok(encode_ppm(&image, &options.palette)),
+        ImageFormat::Bmp => encode_bmp(&image, &options.palette)
+    }
+}
+
+// Two pixels to a byte, the leftmost in the high nibble, with each row padded out to row_stride bytes
+fn pack_nibble_rows(pixels : &[u8], width : usize, row_stride : usize, bottom_up : bool) -> Vec<u8> {
+    let height = pixels.len() / width;
+    let mut packed = vec![0u8 ; row_stride * height];
+    for (y, source_row) in pixels.chunks_exact(width).enumerate() {
+        let dest_y = if bottom_up { height - 1 - y } else { y };
+        let dest_row = &mut packed[dest_y * row_stride..][..row_stride];
+        for (x, index) in source_row.iter().enumerate() {
+            dest_row[x / 2] |= (index & 0x0F) << if x % 2 == 0 { 4 } else { 0 };
+        }
+    }
+    packed
+}
+
+// Sets up a PNG for either kind of image, returning the encoded image data that goes with it
+fn png_encoder<'a>(data : &'a mut Vec<u8>, image : &ScaledImage, palette : &Palette) -> png::Encoder<'a, &'a mut Vec<u8>> {
+    let mut encoder = png::Encoder::new(data, image.width as u32, image.height as u32);
+    match image.pixels {
+        UpscaledPixels::Indexed(_) => {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Four);
+            encoder.set_palette(palette_bytes(palette));
+        },
+        UpscaledPixels::Rgb(_) => {
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+        }
+    }
+    encoder
+}
+
+fn png_image_data(image : &ScaledImage, palette : &Palette) -> Vec<u8> {
+    match &image.pixels {
+        UpscaledPixels::Indexed(pixels) => pack_nibble_rows(pixels, image.width, image.width.div_ceil(2), false),
+        UpscaledPixels::Rgb(_) => image.to_rgb_bytes(palette)
+    }
+}
+
+fn encode_png(image : &ScaledImage, palette : &Palette) -> Result<Vec<u8>, AgiError> {
+    let mut data = vec![];
+
+    let to_export_error = |err : png::EncodingError| AgiError::Export(format!("PNG encoding failed: {}", err));
+    let mut writer = png_encoder(&mut data, image, palette).write_header().map_err(to_export_error)?;
+    writer.write_image_data(&png_image_data(image, palette)).map_err(to_export_error)?;
+    writer.finish().map_err(to_export_error)?;
+
+    Ok(data)
+}
+
+fn encode_ppm(image : &ScaledImage, palette : &Palette) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    data.extend_from_slice(&image.to_rgb_bytes(palette));
+    data
+}
+
+fn encode_bmp(image : &ScaledImage, palette : &Palette) -> Result<Vec<u8>, AgiError> {
+    const FILE_HEADER_SIZE : usize = 14;
+    const INFO_HEADER_SIZE : usize = 40;
+    const PALETTE_SIZE : usize = 16 * 4;
+
+    let UpscaledPixels::Indexed(pixels) = &image.pixels else {
+        return Err(AgiError::Export(String::from("BMPs are exported with 16 colors, which can't hold the blended colors of this upscaler")));
+    };
+
+    // BMP rows are stored bottom to top, each padded to a multiple of 4 bytes
+    let row_stride = image.width.div_ceil(8) * 4;
+    let pixel_data = pack_nibble_rows(pixels, image.width, row_stride, true);
+    let pixel_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + PALETTE_SIZE;
+
+    let mut data = Vec::with_capacity(pixel_offset + pixel_data.len());
+
+    // BITMAPFILEHEADER
+    data.extend_from_slice(b"BM");
+    let _ = data.write_u32::<LittleEndian>((pixel_offset + pixel_data.len()) as u32);
+    let _ = data.write_u32::<LittleEndian>(0); // Reserved
+    let _ = data.write_u32::<LittleEndian>(pixel_offset as u32);
+
+    // BITMAPINFOHEADER
+    let _ = data.write_u32::<LittleEndian>(INFO_HEADER_SIZE as u32);
+    let _ = data.write_i32::<LittleEndian>(image.width as i32);
+    let _ = data.write_i32::<LittleEndian>(image.height as i32);
+    let _ = data.write_u16::<LittleEndian>(1); // Planes
+    let _ = data.write_u16::<LittleEndian>(4); // Bits per pixel
+    let _ = data.write_u32::<LittleEndian>(0); // BI_RGB, uncompressed
+    let _ = data.write_u32::<LittleEndian>(pixel_data.len() as u32);
+    let _ = data.write_i32::<LittleEndian>(2835); // 72 DPI
+    let _ = data.write_i32::<LittleEndian>(2835);
+    let _ = data.write_u32::<LittleEndian>(16); // Colors used
+    let _ = data.write_u32::<LittleEndian>(0); // Colors important, 0 being all of them
+
+    // Palette, as BGR plus a reserved byte
+    for color in palette.colors.iter() {
+        data.extend_from_slice(&[color.b(), color.g(), color.r(), 0]);
+    }
+
+    data.extend_from_slice(&pixel_data);
+    Ok(data)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng
+}
+
+impl AnimationFormat {
+    pub const ALL : [AnimationFormat ; 2] = [AnimationFormat::Gif, AnimationFormat::Apng];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gif => "GIF",
+            Self::Apng => "APNG"
+        }
+    }
+
+    // APNGs are PNGs as far as everything else is concerned, so they keep the usual extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Apng => "png"
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AnimationExportOptions {
+    pub format : AnimationFormat,
+    pub instructions_per_frame : usize,
+    pub frame_delay_ms : u16,
+    pub final_frame_delay_ms : u16, // How long to hold the finished picture before the animation loops
+    pub image : ImageExportOptions // The format here is ignored, but everything else applies to each frame
+}
+
+impl Default for AnimationExportOptions {
+    fn default() -> Self {
+        Self {
+            format : AnimationFormat::Gif,
+            instructions_per_frame : 1,
+            frame_delay_ms : 100,
+            final_frame_delay_ms : 3000,
+            image : ImageExportOptions::default()
+        }
+    }
+}
+
+/*
+Encodes an animation of a picture being drawn, starting from the blank buffer, with a frame after every
+instructions_per_frame instructions and always one of the finished picture.  Frames are encoded as they're rendered
+rather than all being kept around, since a big upscale of a complex picture would otherwise need a lot of memory.
+*/
+pub fn export_animation(instructions : &[DerivedPicRenderInstruction], buffer_type : PictureBufferType, options : &AnimationExportOptions) -> Result<Vec<u8>, AgiError> {
+    let step = options.instructions_per_frame.max(1);
+    let frame_count = 1 + instructions.len() / step + if instructions.len().is_multiple_of(step) { 0 } else { 1 };
+
+    // Frames are given from render_in_steps, so the only way to tell the last one is by counting
+    let mut frame_index = 0;
+    let mut frame_delay_ms = || {
+        frame_index += 1;
+        if frame_index == frame_count { options.final_frame_delay_ms } else { options.frame_delay_ms }
+    };
+
+    let frame_image = |pic_buffer : &PixelBuffer, pri_buffer : &PixelBuffer| {
+        let buffer = match buffer_type {
+            PictureBufferType::Picture => pic_buffer,
+            PictureBufferType::Priority => pri_buffer
+        };
+        ScaledImage::new(buffer, &options.image)
+    };
+
+    // Every frame is the same size and kind of image, so set up the encoders based on a blank one
+    let blank_image = ScaledImage::new(&PixelBuffer::new(PIC_BUFFER_BASE_COLOR), &options.image)?;
+    let (width, height) = (blank_image.width, blank_image.height);
+
+    let mut data = vec![];
+
+    match options.format {
+        AnimationFormat::Gif => {
+            if width > u16::MAX as usize || height > u16::MAX as usize {
+                return Err(AgiError::Export(format!("{}x{} is too big for a GIF", width, height)));
+            }
+
+            let to_export_error = |err : gif::EncodingError| AgiError::Export(format!("GIF encoding failed: {}", err));
+            let mut encoder = gif::Encoder::new(&mut data, width as u16, height as u16, &palette_bytes(&options.image.palette)).map_err(to_export_error)?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(to_export_error)?;
+
+            render_in_steps(instructions, step, |_, pic_buffer, pri_buffer| {
+                let image = frame_image(pic_buffer, pri_buffer)?;
+                let mut frame = match image.pixels {
+                    UpscaledPixels::Indexed(pixels) => gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None),
+                    // Blended colors need their own palette for each frame, which is chosen by quantizing the frame
+                    UpscaledPixels::Rgb(_) => gif::Frame::from_rgb_speed(width as u16, height as u16, &image.to_rgb_bytes(&options.image.palette), 10)
+                };
+                // GIF delays are in hundredths of a second
+                frame.delay = frame_delay_ms().div_ceil(10);
+                encoder.write_frame(&frame).map_err(to_export_error)
+            })?;
+        },
+        AnimationFormat::Apng => {
+            let to_export_error = |err : png::EncodingError| AgiError::Export(format!("APNG encoding failed: {}", err));
+            let mut encoder = png_encoder(&mut data, &blank_image, &options.image.palette);
+            encoder.set_animated(frame_count as u32, 0).map_err(to_export_error)?;
+
+            let mut writer = encoder.write_header().map_err(to_export_error)?;
+            render_in_steps(instructions, step, |_, pic_buffer, pri_buffer| {
+                let image = frame_image(pic_buffer, pri_buffer)?;
+                writer.set_frame_delay(frame_delay_ms(), 1000).map_err(to_export_error)?;
+                writer.write_image_data(&png_image_data(&image, &options.image.palette)).map_err(to_export_error)
+            })?;
+            writer.finish().map_err(to_export_error)?;
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_buffer() -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        buffer.set_pixel(0, 0, Some(0x01), InstructionIndex::default()).unwrap();
+        buffer.set_pixel(VIEWPORT_WIDTH - 1, VIEWPORT_HEIGHT - 1, Some(0x0C), InstructionIndex::default()).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn scaled_image_stretches_and_upscales() {
+        let image = ScaledImage::new(&test_buffer(), &ImageExportOptions { stretch : true, scale : 3, ..Default::default() }).unwrap();
+        assert_eq!((image.width, image.height), (VIEWPORT_WIDTH * 6, VIEWPORT_HEIGHT * 3));
+        let UpscaledPixels::Indexed(pixels) = &image.pixels else { panic!("Expected palette indexes") };
+        assert!(pixels[..6].iter().all(|c| *c == 0x01));
+        assert_eq!(pixels[6], PIC_BUFFER_BASE_COLOR);
+        assert!(pixels[2 * image.width..][..6].iter().all(|c| *c == 0x01));
+        assert_eq!(pixels[3 * image.width], PIC_BUFFER_BASE_COLOR);
+        assert_eq!(*pixels.last().unwrap(), 0x0C);
+
+        assert!(ScaledImage::new(&test_buffer(), &ImageExportOptions { scale : 0, ..Default::default() }).is_err());
+
+        // Upscalers go before the stretch, so a 2x upscale stretched is 4x as wide
+        let image = ScaledImage::new(&test_buffer(), &ImageExportOptions { upscaler : Upscaler::Xbr, stretch : true, ..Default::default() }).unwrap();
+        assert_eq!((image.width, image.height), (VIEWPORT_WIDTH * 4, VIEWPORT_HEIGHT * 2));
+        assert!(matches!(image.pixels, UpscaledPixels::Rgb(_)));
+        assert!(export_buffer(&test_buffer(), &ImageExportOptions { format : ImageFormat::Bmp, upscaler : Upscaler::Xbr, ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn png_round_trips_palette_indexes() {
+        let options = ImageExportOptions { stretch : true, ..Default::default() };
+        let data = export_buffer(&test_buffer(), &options).unwrap();
+
+        let mut decoder = png::Decoder::new(data.as_slice());
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let mut decoded = vec![0u8 ; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+
+        assert_eq!((info.width, info.height), (320, 168));
+        assert_eq!((info.color_type, info.bit_depth), (png::ColorType::Indexed, png::BitDepth::Four));
+        assert_eq!(decoded[0], 0x11);
+        assert_eq!(decoded[1], 0xFF);
+        assert_eq!(decoded[info.line_size * 168 - 1], 0xCC);
+    }
+
+    #[test]
+    fn bmp_and_ppm_headers_match_the_image() {
+        let bmp = export_buffer(&test_buffer(), &ImageExportOptions { format : ImageFormat::Bmp, ..Default::default() }).unwrap();
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(LittleEndian::read_u32(&bmp[2..6]) as usize, bmp.len());
+        assert_eq!(LittleEndian::read_i32(&bmp[18..22]), VIEWPORT_WIDTH as i32);
+        assert_eq!(LittleEndian::read_i32(&bmp[22..26]), VIEWPORT_HEIGHT as i32);
+        // Bottom-up, so the first row of pixel data is the bottom of the picture
+        let pixel_offset = LittleEndian::read_u32(&bmp[10..14]) as usize;
+        assert_eq!(bmp[pixel_offset + VIEWPORT_WIDTH / 2 - 1], 0xFC);
+        assert_eq!(bmp[bmp.len() - VIEWPORT_WIDTH / 2], 0x1F);
+
+        let ppm = export_buffer(&test_buffer(), &ImageExportOptions { format : ImageFormat::Ppm, ..Default::default() }).unwrap();
+        let header = b"P6\n160 168\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + VIEWPORT_PIXELS * 3);
+        assert_eq!(&ppm[header.len()..][..3], &[0x00, 0x00, 0xAA]);
+    }
+
+    #[test]
+    fn animations_have_a_frame_per_step() {
+        // Four drawing instructions after the color is set, so five instructions in all
+        let pic = PicResource::new(&[0xF0, 0x01, 0xF6, 0, 0, 10, 10, 0xF6, 0, 10, 10, 0, 0xF6, 20, 0, 20, 10, 0xF6, 30, 0, 30, 10]).unwrap();
+
+        let options = AnimationExportOptions { instructions_per_frame : 2, ..Default::default() };
+        let data = export_animation(pic.get_instructions(), PictureBufferType::Picture, &options).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(data.as_slice()).unwrap();
+        let mut delays = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        // Blank, after 2, after 4, and the finished picture after 5
+        assert_eq!(delays, vec![10, 10, 10, 300]);
+
+        let options = AnimationExportOptions { format : AnimationFormat::Apng, ..Default::default() };
+        let data = export_animation(pic.get_instructions(), PictureBufferType::Priority, &options).unwrap();
+        let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let animation = reader.info().animation_control().unwrap();
+        assert_eq!((animation.num_frames, animation.num_plays), (6, 0));
+    }
+}
@@ -0,0 +1,1353 @@
+use std::collections::{HashMap, HashSet};
+use egui::*;
+use crate::*;
+
+/// Debugging aids for the viewer, the defaults render a picture as the game would
+#[derive(Default)]
+pub struct RenderOptions {
+    pub render_only_selected_instruction : bool,
+    pub show_fill_outlines : bool,
+    /// Only draw this many of the last instruction's lines or fill points, to step through an instruction part by part
+    pub last_instruction_sub_items : Option<usize>,
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct InstructionIndex {
+    base_index : u16,
+    sub_index : u16
+}
+
+// Internally used to track the edges of a fill
+#[derive(PartialEq, Eq)]
+enum FillEdge {
+    Line(InstructionIndex),
+    TopBorder,
+    RightBorder,
+    BottomBorder,
+    LeftBorder
+}
+
+impl FillEdge {
+
+    const MAX_X : f32 = (VIEWPORT_WIDTH-1) as f32;
+    const MAX_Y : f32 = (VIEWPORT_HEIGHT-1) as f32;
+    const TOP_LEFT : Pos2 = pos2(0f32, 0f32);
+    const TOP_RIGHT : Pos2 = pos2(Self::MAX_X, 0f32);
+    const BOTTOM_RIGHT : Pos2 = pos2(Self::MAX_X, Self::MAX_Y);
+    const BOTTOM_LEFT : Pos2 = pos2(0f32, Self::MAX_Y);
+
+    const TOP_BORDER : [Pos2; 2] = [Self::TOP_LEFT, Self::TOP_RIGHT];
+    const RIGHT_BORDER : [Pos2; 2] = [Self::TOP_RIGHT, Self::BOTTOM_RIGHT];
+    const BOTTOM_BORDER : [Pos2; 2] = [Self::BOTTOM_RIGHT, Self::BOTTOM_LEFT];
+    const LEFT_BORDER : [Pos2; 2] = [Self::BOTTOM_LEFT, Self::TOP_LEFT];
+
+    // The line along this edge, or None if the fill was bounded by the pixels of an earlier fill rather than a line
+    pub fn to_line(&self, instructions : &[DerivedPicRenderInstruction]) -> Result<Option<[Pos2; 2]>, AgiError> {
+        match self {
+            Self::Line(inst) => {
+                if (inst.base_index as usize) < instructions.len() {
+                    let bi = inst.base_index as usize;
+                    match &instructions[bi] {
+                        DerivedPicRenderInstruction::DrawLines(_, points) => {
+                            let si = inst.sub_index as usize;
+                            if points.len() == 1 && si == 0 {
+                                // Special case for single pixel lines
+                                let point = points[si].clamped_to_viewport().to_pos2();
+                                Ok(Some([point, point]))
+                            } else if si + 1 < points.len() {
+                                Ok(Some([points[si].clamped_to_viewport().to_pos2(), points[si+1].clamped_to_viewport().to_pos2()]))
+                            } else {
+                                Err(AgiError::Render("Sub index was out of bounds".to_string()))
+                            }
+                        },
+                        DerivedPicRenderInstruction::Fill(_, _) => Ok(None),
+                        _ => Err(AgiError::Render("Fill instruction adjacency touched a non line instruction".to_string()))
+
+                    }
+                    
+                } else {
+                    Err(AgiError::Render("Instruction index was out of range!".to_string()))
+                }
+            },
+            Self::TopBorder => Ok(Some(Self::TOP_BORDER)),
+            Self::RightBorder => Ok(Some(Self::RIGHT_BORDER)),
+            Self::BottomBorder => Ok(Some(Self::BOTTOM_BORDER)),
+            Self::LeftBorder => Ok(Some(Self::LEFT_BORDER))
+        }
+    }
+}
+
+/* 
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct LineU8 {
+    start : [u8; 2],
+    end : [u8 ; 2]
+}
+
+fn line_u8(start_x : u8, start_y : u8, end_x : u8, end_y : u8) -> LineU8 {
+    LineU8 {
+        start: [start_x, start_y],
+        end: [end_x, end_y]
+    }
+}
+*/
+
+impl InstructionIndex {
+    pub fn new_sub(base_index : usize, sub_index : usize) -> Self {
+        Self {
+            base_index : base_index as u16,
+            sub_index : sub_index as u16
+        }
+    }
+
+    // The instruction that drew a pixel
+    pub fn get_base_index(&self) -> usize {
+        self.base_index as usize
+    }
+
+    // Which of the instruction's lines or fill points drew a pixel
+    pub fn get_sub_index(&self) -> usize {
+        self.sub_index as usize
+    }
+
+    // Packs an optional index into a u32 for compact storage, there can't be 65535 instructions each with 65535
+    // parts in a PIC resource so all ones is free to mean None
+    fn pack(index : &Option<InstructionIndex>) -> u32 {
+        match index {
+            Some(index) => ((index.base_index as u32) << 16) | index.sub_index as u32,
+            None => u32::MAX
+        }
+    }
+
+    fn unpack(packed : u32) -> Option<InstructionIndex> {
+        if packed == u32::MAX {
+            None
+        } else {
+            Some(InstructionIndex { base_index : (packed >> 16) as u16, sub_index : (packed & 0xFFFF) as u16 })
+        }
+    }
+}
+
+pub struct VectorPath {
+    pub points : Vec<Pos2>,
+    pub agi_color : u8,
+    pub instruction_index : usize
+}
+
+impl VectorPath {
+    pub fn from_point_list(points : &[PosU8], color : u8, instruction_index : usize) -> VectorPath {
+        VectorPath { 
+            points: points.iter().map(|p| pos2(p.x as f32, p.y as f32)).collect::<Vec<Pos2>>(), 
+            agi_color: color,
+            instruction_index
+        }
+    }
+
+    pub fn from_line(points : [Pos2; 2], color : u8, instruction_index : usize) -> VectorPath {
+        VectorPath {
+            points: points.to_vec(),
+            agi_color: color,
+            instruction_index
+        }
+    }
+}
+
+pub struct VectorFill {
+    pub vertices : Vec<Pos2>,
+    pub triangles : Vec<usize>, // Indexed into vertices, three per triangle
+    pub outlines : Vec<Vec<Pos2>>, // Closed polygons around the fill, holes wind the opposite way to the outside edge
+    pub agi_color : u8,
+    pub instruction_index : usize
+}
+
+impl VectorFill {
+    /*
+    Traces the area covered by one seed point of a fill into polygons.  Rather than following the pixel edges, the
+    polygons run through the centers of the pixels bordering the fill, which are the same points the vector lines run
+    through, so the fill meets its bounding lines without gaps.  Where the fill runs into the edge of the picture it
+    stops at the outermost pixel centers, the same place as the FillEdge border lines.
+
+    This works on the grid of cells between four neighbouring pixel centers: a cell is solid if all four of its corners
+    are inside (filled or bordering the fill), and a triangle if three are, which cuts the corners of diagonal lines.
+    That gives the triangulation directly, holes included, and the outlines are whatever cell edges aren't shared.
+    */
+    pub fn from_fill_pixels(buffer : &PixelBuffer, fill_index : InstructionIndex, color : u8) -> Option<VectorFill> {
+        let filled = |x : usize, y : usize| buffer.instruction_indexes[y * VIEWPORT_WIDTH + x] == Some(fill_index);
+
+        // Only look at the part of the picture around the fill
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (VIEWPORT_WIDTH, VIEWPORT_HEIGHT, 0, 0);
+        for y in 0..VIEWPORT_HEIGHT {
+            for x in 0..VIEWPORT_WIDTH {
+                if filled(x, y) {
+                    (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+                }
+            }
+        }
+
+        if min_x > max_x {
+            return None;
+        }
+
+        let (min_x, min_y) = (min_x.saturating_sub(1), min_y.saturating_sub(1));
+        let (max_x, max_y) = ((max_x + 1).min(VIEWPORT_WIDTH - 1), (max_y + 1).min(VIEWPORT_HEIGHT - 1));
+
+        let inside = |x : usize, y : usize| -> bool {
+            (y.saturating_sub(1)..=(y + 1).min(VIEWPORT_HEIGHT - 1)).any(|ny| {
+                (x.saturating_sub(1)..=(x + 1).min(VIEWPORT_WIDTH - 1)).any(|nx| filled(nx, ny))
+            })
+        };
+
+        let width = max_x - min_x + 1;
+        let inside_mask : Vec<bool> = (min_y..=max_y).flat_map(|y| (min_x..=max_x).map(move |x| (x, y))).map(|(x, y)| inside(x, y)).collect();
+
+        let mut fill = VectorFill {
+            vertices : vec![],
+            triangles : vec![],
+            outlines : vec![],
+            agi_color : color,
+            instruction_index : fill_index.base_index as usize
+        };
+        let mut vertex_lookup : HashMap<(usize, usize), usize> = HashMap::new();
+        let mut add_vertex = |fill : &mut VectorFill, (x, y) : (usize, usize)| -> usize {
+            *vertex_lookup.entry((x, y)).or_insert_with(|| {
+                fill.vertices.push(pos2(x as f32, y as f32));
+                fill.vertices.len() - 1
+            })
+        };
+
+        // Outline edges, an edge cancels out when the neighbouring cell has the same edge going the other way
+        let mut outline_edges : HashSet<((usize, usize), (usize, usize))> = HashSet::new();
+
+        for y in min_y..max_y {
+            let mut solid_run_start : Option<usize> = None;
+
+            for x in min_x..=max_x {
+                // Corners in clockwise order (in screen space), so every triangle and outline winds the same way
+                let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+                let cell_corners : Vec<(usize, usize)> = if x == max_x {
+                    vec![]
+                } else {
+                    corners.iter().copied().filter(|(cx, cy)| inside_mask[(cy - min_y) * width + (cx - min_x)]).collect()
+                };
+
+                // Merge runs of solid cells into a single quad to keep the triangle count down
+                if cell_corners.len() == 4 {
+                    solid_run_start.get_or_insert(x);
+                } else if let Some(run_start) = solid_run_start.take() {
+                    let quad = [(run_start, y), (x, y), (x, y + 1), (run_start, y + 1)].map(|v| add_vertex(&mut fill, v));
+                    fill.triangles.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                }
+
+                if cell_corners.len() == 3 {
+                    let triangle = [cell_corners[0], cell_corners[1], cell_corners[2]].map(|v| add_vertex(&mut fill, v));
+                    fill.triangles.extend(triangle);
+                }
+
+                if cell_corners.len() >= 3 {
+                    for (i, from) in cell_corners.iter().enumerate() {
+                        let to = cell_corners[(i + 1) % cell_corners.len()];
+                        if !outline_edges.remove(&(to, *from)) {
+                            outline_edges.insert((*from, to));
+                        }
+                    }
+                }
+            }
+        }
+
+        fill.outlines = chain_outline_edges(outline_edges);
+
+        Some(fill)
+    }
+}
+
+// Joins the unshared cell edges of a fill up into closed loops, dropping the points in the middle of straight runs
+fn chain_outline_edges(edges : HashSet<((usize, usize), (usize, usize))>) -> Vec<Vec<Pos2>> {
+    let mut next_points : HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    for (from, to) in edges {
+        next_points.entry(from).or_default().push(to);
+    }
+
+    // Start each loop from its top left point, so the results don't depend on hash ordering
+    let mut starts : Vec<(usize, usize)> = next_points.keys().copied().collect();
+    starts.sort_by_key(|(x, y)| (*y, *x));
+
+    let mut outlines = vec![];
+    for start in starts {
+        let mut outline : Vec<(usize, usize)> = vec![];
+        let mut point = start;
+
+        while let Some(next) = next_points.get_mut(&point).and_then(|n| n.pop()) {
+            outline.push(point);
+            point = next;
+        }
+
+        if outline.len() < 3 {
+            continue;
+        }
+
+        let is_corner = |i : usize| {
+            let (prev, cur, next) = (outline[(i + outline.len() - 1) % outline.len()], outline[i], outline[(i + 1) % outline.len()]);
+            let cross = (cur.0 as i32 - prev.0 as i32) * (next.1 as i32 - cur.1 as i32) - (cur.1 as i32 - prev.1 as i32) * (next.0 as i32 - cur.0 as i32);
+            cross != 0
+        };
+
+        outlines.push((0..outline.len()).filter(|i| is_corner(*i)).map(|i| pos2(outline[i].0 as f32, outline[i].1 as f32)).collect());
+    }
+
+    outlines
+}
+
+#[derive(Default)]
+pub struct ShapeBuffer {
+    paths : Vec<VectorPath>,
+    fills : Vec<VectorFill>
+}
+
+impl ShapeBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.fills.clear();
+    }
+
+    pub fn add_path(&mut self, path : VectorPath) {
+        self.paths.push(path)
+    }
+
+    pub fn get_paths(&self) -> &Vec<VectorPath> {
+        &self.paths
+    }
+
+    pub fn add_fill(&mut self, fill : VectorFill) {
+        self.fills.push(fill)
+    }
+
+    pub fn get_fills(&self) -> &Vec<VectorFill> {
+        &self.fills
+    }
+}
+
+/// A 160x168 screen of AGI colour indexes, along with the instruction that last drew each pixel
+pub struct PixelBuffer {
+    // AGI palette indexes rather than RGB, the fill compares against these for every pixel it touches
+    pixels : Box<[u8 ; VIEWPORT_PIXELS]>,
+    instruction_indexes : Box<[Option<InstructionIndex> ; VIEWPORT_PIXELS]>
+}
+
+impl PixelBuffer {
+    pub fn new(default_color : u8) -> Self {
+        Self {
+            pixels : vec![default_color ; VIEWPORT_PIXELS].into_boxed_slice().try_into().unwrap(),
+            instruction_indexes : vec![None ; VIEWPORT_PIXELS].into_boxed_slice().try_into().unwrap(),
+        }
+    }
+
+    fn reset(&mut self, color : u8) {
+        self.pixels.fill(color);
+        self.instruction_indexes.fill(None);
+    }
+
+    fn isolate_instruction_pixels(&mut self, index : usize, sub_index : Option<usize>, mask_color : u8) {
+        for i in 0..VIEWPORT_PIXELS {
+            if let Some(inst) = self.instruction_indexes[i] {
+                if inst.base_index as usize != index || sub_index.is_some_and(|sub_index| inst.sub_index as usize != sub_index) {
+                    self.pixels[i] = mask_color;
+                }
+            }
+        }
+    }
+
+    // Each axis is checked on its own, so an x past the right edge can't wrap onto the next row
+    fn pixel_offset(x : usize, y : usize) -> Result<usize, AgiError> {
+        if x >= VIEWPORT_WIDTH || y >= VIEWPORT_HEIGHT {
+            Err(AgiError::Render(format!("Pixel location ({x},{y}) out of range!")))
+        } else {
+            Ok(y * VIEWPORT_WIDTH + x)
+        }
+    }
+
+    pub fn set_pixel(&mut self, x : usize, y : usize, color : Option<u8>, instruction_index : InstructionIndex) -> Result<(), AgiError> {
+        if let Some(color) = color {
+            let index = Self::pixel_offset(x, y)?;
+            self.pixels[index] = color;
+            self.instruction_indexes[index] = Some(instruction_index);
+        }
+
+        Ok(())
+    }
+
+    pub fn get_pixel_indexes(&self) -> &[u8] {
+        self.pixels.as_ref()
+    }
+
+    pub fn get_pixels_vec(&self) -> Vec<Color32> {
+        self.pixels.iter().map(|c| get_color(*c)).collect()
+    }
+
+    pub fn get_pixel(&self, x : usize, y : usize) -> Result<Color32, AgiError> {
+        Ok(get_color(self.get_pixel_index(x, y)?))
+    }
+
+    pub fn get_pixel_index(&self, x : usize, y : usize) -> Result<u8, AgiError> {
+        Ok(self.pixels[Self::pixel_offset(x, y)?])
+    }
+
+    pub fn get_pixel_instruction(&self, x : usize, y : usize) -> Result<Option<InstructionIndex>, AgiError> {
+        Ok(self.instruction_indexes[Self::pixel_offset(x, y)?])
+    }
+
+    /// Which pixels are a different colour in other, row by row the same as the pixels, e.g. to compare the same
+    /// picture from two releases of a game
+    pub fn get_differences(&self, other : &PixelBuffer) -> Vec<bool> {
+        self.pixels.iter().zip(other.pixels.iter()).map(|(a, b)| a != b).collect()
+    }
+
+}
+
+
+pub fn get_color(agi_color : u8) -> Color32 {
+    Palette::EGA.get_color(agi_color)
+}
+
+// The RGB values used to display the 16 AGI colors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub name : &'static str,
+    pub colors : [Color32 ; 16]
+}
+
+impl Palette {
+    // From here: https://moddingwiki.shikadi.net/wiki/EGA_Palette
+    pub const EGA : Palette = Palette {
+        name : "EGA",
+        colors : [
+            Color32::from_rgb(0x00,0x00,0x00), // black
+            Color32::from_rgb(0x00,0x00,0xAA), // blue
+            Color32::from_rgb(0x00,0xAA,0x00), // green
+            Color32::from_rgb(0x00,0xAA,0xAA), // cyan
+            Color32::from_rgb(0xAA,0x00,0x00), // red
+            Color32::from_rgb(0xAA,0x00,0xAA), // magenta
+            Color32::from_rgb(0xAA,0x55,0x00), // brown
+            Color32::from_rgb(0xAA,0xAA,0xAA), // light gray
+            Color32::from_rgb(0x55,0x55,0x55), // dark gray
+            Color32::from_rgb(0x55,0x55,0xFF), // light blue
+            Color32::from_rgb(0x55,0xFF,0x55), // light green
+            Color32::from_rgb(0x55,0xFF,0xFF), // light cyan
+            Color32::from_rgb(0xFF,0x55,0x55), // light red
+            Color32::from_rgb(0xFF,0x55,0xFF), // light magenta
+            Color32::from_rgb(0xFF,0xFF,0x55), // yellow
+            Color32::from_rgb(0xFF,0xFF,0xFF), // white
+        ]
+    };
+
+    // The EGA colors by luminance, which is handy for print
+    pub const GREYSCALE : Palette = Palette {
+        name : "Greyscale",
+        colors : [
+            Color32::from_gray(0x00), // black
+            Color32::from_gray(0x13), // blue
+            Color32::from_gray(0x64), // green
+            Color32::from_gray(0x77), // cyan
+            Color32::from_gray(0x33), // red
+            Color32::from_gray(0x46), // magenta
+            Color32::from_gray(0x65), // brown
+            Color32::from_gray(0xAA), // light gray
+            Color32::from_gray(0x55), // dark gray
+            Color32::from_gray(0x68), // light blue
+            Color32::from_gray(0xB9), // light green
+            Color32::from_gray(0xCC), // light cyan
+            Color32::from_gray(0x88), // light red
+            Color32::from_gray(0x9B), // light magenta
+            Color32::from_gray(0xEC), // yellow
+            Color32::from_gray(0xFF), // white
+        ]
+    };
+
+    pub const ALL : [Palette ; 2] = [Palette::EGA, Palette::GREYSCALE];
+
+    pub fn get_color(&self, agi_color : u8) -> Color32 {
+        match self.colors.get(agi_color as usize) {
+            Some(color) => *color,
+            None => Color32::from_rgb(0xFF,0x00,0xFF)
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::EGA
+    }
+}
+
+/// Renders a whole picture into whichever of the buffers are given, clearing them first
+pub fn render_to_buffers(
+    instructions : &[DerivedPicRenderInstruction],
+    render_options : &RenderOptions,
+    pic_buffer : &mut Option<&mut PixelBuffer>,
+    pri_buffer : &mut Option<&mut PixelBuffer>,
+    pic_vectors : &mut Option<&mut ShapeBuffer>) -> Result<(), AgiError> {
+
+    with_both_buffers(pic_buffer, pri_buffer, |pic_buffer, pri_buffer| {
+        // Clear pixel buffers
+        pic_buffer.reset(PIC_BUFFER_BASE_COLOR);
+        pri_buffer.reset(PRI_BUFFER_BASE_COLOR);
+
+        if let Some(pic_vectors) = pic_vectors {
+            pic_vectors.clear();
+        }
+
+        let mut colors = DrawColors::default();
+        render_instructions(instructions, 0, &mut colors, render_options, pic_buffer, pri_buffer, pic_vectors, |_, _, _, _| ())?;
+
+        finish_render(instructions.len().saturating_sub(1), render_options, pic_buffer, pri_buffer);
+
+        Ok(())
+    })
+}
+
+// The interpreter's fill looks at both the picture and priority screens to decide where it can spread, so we always
+// render both, using a scratch buffer for whichever one the caller didn't ask for
+fn with_both_buffers<R>(pic_buffer : &mut Option<&mut PixelBuffer>, pri_buffer : &mut Option<&mut PixelBuffer>, render : impl FnOnce(&mut PixelBuffer, &mut PixelBuffer) -> R) -> R {
+    let mut scratch_pic_buffer;
+    let pic_buffer : &mut PixelBuffer = match pic_buffer {
+        Some(pic_buffer) => pic_buffer,
+        None => {
+            scratch_pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+            &mut scratch_pic_buffer
+        }
+    };
+
+    let mut scratch_pri_buffer;
+    let pri_buffer : &mut PixelBuffer = match pri_buffer {
+        Some(pri_buffer) => pri_buffer,
+        None => {
+            scratch_pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+            &mut scratch_pri_buffer
+        }
+    };
+
+    render(pic_buffer, pri_buffer)
+}
+
+// The colors set by the most recent SetColor instructions, None meaning drawing to that screen is disabled
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct DrawColors {
+    pic : Option<u8>,
+    pri : Option<u8>
+}
+
+impl Default for DrawColors {
+    fn default() -> Self {
+        Self { pic : Some(PIC_BUFFER_BASE_COLOR), pri : Some(PRI_BUFFER_BASE_COLOR) }
+    }
+}
+
+// Renders instructions[first_instruction..] on top of whatever is in the buffers already, calling after_instruction
+// with the state of the render once each instruction is applied
+#[allow(clippy::too_many_arguments)]
+fn render_instructions(
+    instructions : &[DerivedPicRenderInstruction],
+    first_instruction : usize,
+    colors : &mut DrawColors,
+    render_options : &RenderOptions,
+    pic_buffer : &mut PixelBuffer,
+    pri_buffer : &mut PixelBuffer,
+    pic_vectors : &mut Option<&mut ShapeBuffer>,
+    mut after_instruction : impl FnMut(usize, &DrawColors, &PixelBuffer, &PixelBuffer)) -> Result<(), AgiError> {
+
+    let latest_instruction_index = instructions.len().saturating_sub(1);
+
+    // Option aliases
+    let only_latest_instruction = render_options.render_only_selected_instruction;
+    let show_fill_outlines = render_options.show_fill_outlines;
+
+    // Actual rendering
+    for (instruction_index, instruction) in instructions.iter().enumerate().skip(first_instruction) {
+        let render_instruction = !only_latest_instruction || instruction_index == latest_instruction_index;
+        let sub_items = render_options.last_instruction_sub_items.filter(|_| instruction_index == latest_instruction_index);
+        match instruction {
+            DerivedPicRenderInstruction::SetColor(_, buffer_type, color) => {
+                // There are only 16 colours, and like the interpreter only the low 4 bits of the argument are used
+                let color = color.map(|color| color & 0x0F);
+                match buffer_type {
+                    PictureBufferType::Picture => colors.pic = color,
+                    PictureBufferType::Priority => colors.pri = color
+                }
+            },
+            DerivedPicRenderInstruction::DrawLines(_, lines) => {
+                // The points are kept as they were in the resource, but drawn on the nearest edge like the interpreter
+                let lines : Vec<PosU8> = get_sub_item_points(lines, sub_items, true).iter().map(|point| point.clamped_to_viewport()).collect();
+                draw_pixel_lines(&lines, pic_buffer, colors.pic, pri_buffer, colors.pri, instruction_index)?;
+
+                // For vectors, only place the latest instruction if requested
+                if render_instruction {
+                    if let (Some(pic_vectors), Some(pic_color)) = (&mut *pic_vectors, colors.pic) {
+                        pic_vectors.add_path(VectorPath::from_point_list(&lines, pic_color, instruction_index));
+                    }
+                }
+            },
+            DerivedPicRenderInstruction::Fill(_, points) => {
+                let points = get_sub_item_points(points, sub_items, false);
+
+                // Tracking the fill edges is relatively expensive, so only do it if they're going to be used
+                let track_edges = render_instruction && show_fill_outlines && pic_vectors.is_some();
+                let pic_edges = pixel_fill(points, pic_buffer, colors.pic, pri_buffer, colors.pri, instruction_index, track_edges)?;
+
+                // For vectors, only place the latest instruction if requested
+                if render_instruction {
+                    if let (Some(pic_vectors), Some(pic_color)) = (&mut *pic_vectors, colors.pic) {
+                        for sub_index in 0..points.len() {
+                            if let Some(fill) = VectorFill::from_fill_pixels(pic_buffer, InstructionIndex::new_sub(instruction_index, sub_index), pic_color) {
+                                pic_vectors.add_fill(fill);
+                            }
+                        }
+                    }
+                }
+
+                if track_edges {
+                    if let (Some(pic_vectors), Some(pic_color)) = (&mut *pic_vectors, colors.pic) {
+                        add_fill_outlines(pic_edges, instructions, pic_color, instruction_index, pic_vectors)?;
+                    }
+                }
+            },
+            DerivedPicRenderInstruction::Unimplemented(_orignal_inst) => {
+                // TODO: Log?
+            }
+        }
+
+        after_instruction(instruction_index, colors, pic_buffer, pri_buffer);
+    }
+
+    Ok(())
+}
+
+// Adds the lines and screen borders that bounded a fill to the vectors, as its outline
+fn add_fill_outlines(pic_edges : FillEdgeLists, instructions : &[DerivedPicRenderInstruction], pic_color : u8, instruction_index : usize, pic_vectors : &mut ShapeBuffer) -> Result<(), AgiError> {
+    for pic_edge_list in pic_edges {
+        for pic_edge in pic_edge_list {
+            if let Some(line) = pic_edge.to_line(instructions)? {
+                pic_vectors.add_path(VectorPath::from_line(line, pic_color, instruction_index));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The points that draw the first sub_items lines or fill points of an instruction, lines needing one more point than
+// there are lines
+fn get_sub_item_points(points : &[PosU8], sub_items : Option<usize>, is_lines : bool) -> &[PosU8] {
+    let point_count = match sub_items {
+        Some(sub_items) if is_lines && sub_items > 0 => sub_items + 1,
+        Some(sub_items) => sub_items,
+        None => points.len()
+    };
+
+    &points[..point_count.min(points.len())]
+}
+
+/// Renders the whole picture, calling on_step with the number of instructions drawn so far and the buffers as they are
+/// at that point, first for the blank buffers, then after every step instructions, and finally once the picture is done
+pub fn render_in_steps(
+    instructions : &[DerivedPicRenderInstruction],
+    step : usize,
+    mut on_step : impl FnMut(usize, &PixelBuffer, &PixelBuffer) -> Result<(), AgiError>) -> Result<(), AgiError> {
+
+    let step = step.max(1);
+
+    let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+    let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+    on_step(0, &pic_buffer, &pri_buffer)?;
+
+    if instructions.is_empty() {
+        return Ok(());
+    }
+
+    // The callback from render_instructions can't fail, so hold on to the first error and stop calling on_step
+    let mut result = Ok(());
+    let mut colors = DrawColors::default();
+    render_instructions(instructions, 0, &mut colors, &RenderOptions::default(), &mut pic_buffer, &mut pri_buffer, &mut None, |instruction_index, _, pic_buffer, pri_buffer| {
+        let instruction_count = instruction_index + 1;
+        if result.is_ok() && (instruction_count % step == 0 || instruction_count == instructions.len()) {
+            result = on_step(instruction_count, pic_buffer, pri_buffer);
+        }
+    })?;
+
+    result
+}
+
+fn finish_render(latest_instruction_index : usize, render_options : &RenderOptions, pic_buffer : &mut PixelBuffer, pri_buffer : &mut PixelBuffer) {
+    if render_options.render_only_selected_instruction {
+        // Mask out pixels from other instructions, and from the earlier parts of the last one if it's only partly drawn
+        let sub_index = render_options.last_instruction_sub_items.and_then(|sub_items| sub_items.checked_sub(1));
+        pic_buffer.isolate_instruction_pixels(latest_instruction_index, sub_index, PIC_BUFFER_BASE_COLOR);
+        pri_buffer.isolate_instruction_pixels(latest_instruction_index, sub_index, PRI_BUFFER_BASE_COLOR);
+    }
+}
+
+// The state of a render after a given number of instructions, kept compact since a complex picture can have dozens
+struct RenderSnapshot {
+    instruction_count : usize,
+    colors : DrawColors,
+    // Picture color in the high nibble, priority color in the low nibble, which holds since colours are masked to 4 bits
+    pixels : Box<[u8]>,
+    pic_instruction_indexes : Box<[u32]>,
+    pri_instruction_indexes : Box<[u32]>
+}
+
+impl RenderSnapshot {
+    fn capture(instruction_count : usize, colors : &DrawColors, pic_buffer : &PixelBuffer, pri_buffer : &PixelBuffer) -> Self {
+        Self {
+            instruction_count,
+            colors : *colors,
+            pixels : pic_buffer.pixels.iter().zip(pri_buffer.pixels.iter()).map(|(pic, pri)| (pic << 4) | (pri & 0x0F)).collect(),
+            pic_instruction_indexes : pic_buffer.instruction_indexes.iter().map(InstructionIndex::pack).collect(),
+            pri_instruction_indexes : pri_buffer.instruction_indexes.iter().map(InstructionIndex::pack).collect()
+        }
+    }
+
+    fn restore(&self, pic_buffer : &mut PixelBuffer, pri_buffer : &mut PixelBuffer) {
+        for (i, pixel) in self.pixels.iter().enumerate() {
+            pic_buffer.pixels[i] = pixel >> 4;
+            pri_buffer.pixels[i] = pixel & 0x0F;
+            pic_buffer.instruction_indexes[i] = InstructionIndex::unpack(self.pic_instruction_indexes[i]);
+            pri_buffer.instruction_indexes[i] = InstructionIndex::unpack(self.pri_instruction_indexes[i]);
+        }
+    }
+}
+
+pub const DEFAULT_SNAPSHOT_INTERVAL : usize = 32;
+
+/*
+Checkpoints of the render of a picture every few instructions, so that rendering the state after any instruction only
+has to replay the instructions since the nearest earlier checkpoint, rather than the whole picture.  These are tied to
+the instructions they were created from, and need to be recreated if those change.
+*/
+pub struct RenderSnapshots {
+    instruction_count : usize,
+    snapshots : Vec<RenderSnapshot>
+}
+
+impl RenderSnapshots {
+    pub fn new(instructions : &[DerivedPicRenderInstruction], interval : usize) -> Result<Self, AgiError> {
+        let interval = interval.max(1);
+
+        let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+        pic_buffer.reset(PIC_BUFFER_BASE_COLOR);
+        pri_buffer.reset(PRI_BUFFER_BASE_COLOR);
+
+        let mut colors = DrawColors::default();
+        let mut snapshots = vec![RenderSnapshot::capture(0, &colors, &pic_buffer, &pri_buffer)];
+
+        if !instructions.is_empty() {
+            render_instructions(instructions, 0, &mut colors, &RenderOptions::default(), &mut pic_buffer, &mut pri_buffer, &mut None, |instruction_index, colors, pic_buffer, pri_buffer| {
+                let instruction_count = instruction_index + 1;
+                if instruction_count % interval == 0 {
+                    snapshots.push(RenderSnapshot::capture(instruction_count, colors, pic_buffer, pri_buffer));
+                }
+            })?;
+        }
+
+        Ok(Self {
+            instruction_count : instructions.len(),
+            snapshots
+        })
+    }
+
+    pub fn get_instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    // The same as render_to_buffers(&instructions[0..=instruction_index], ...), but starting from the closest snapshot
+    pub fn render_after_instruction(
+        &self,
+        instructions : &[DerivedPicRenderInstruction],
+        instruction_index : usize,
+        render_options : &RenderOptions,
+        pic_buffer : &mut Option<&mut PixelBuffer>,
+        pri_buffer : &mut Option<&mut PixelBuffer>,
+        pic_vectors : &mut Option<&mut ShapeBuffer>) -> Result<(), AgiError> {
+
+        if instructions.len() != self.instruction_count {
+            return Err(AgiError::Render(format!("Snapshots were taken of {} instructions, but were given {}", self.instruction_count, instructions.len())));
+        }
+
+        if instruction_index >= instructions.len() {
+            return Err(AgiError::Render(format!("Instruction index {} out of range, there are only {} instructions", instruction_index, instructions.len())));
+        }
+
+        let instructions = &instructions[0..=instruction_index];
+
+        if pic_vectors.is_some() {
+            // The vectors are built up from every instruction, so these always need a full render
+            return render_to_buffers(instructions, render_options, pic_buffer, pri_buffer, pic_vectors);
+        }
+
+        // A partly drawn last instruction has to be replayed, so it can't come from a snapshot
+        let last_snapshot_count = if render_options.last_instruction_sub_items.is_some() { instructions.len() - 1 } else { instructions.len() };
+        let snapshot = self.snapshots.iter()
+            .rev()
+            .find(|s| s.instruction_count <= last_snapshot_count)
+            .ok_or_else(|| AgiError::Render("Missing initial snapshot".to_string()))?;
+
+        with_both_buffers(pic_buffer, pri_buffer, |pic_buffer, pri_buffer| {
+            snapshot.restore(pic_buffer, pri_buffer);
+
+            let mut colors = snapshot.colors;
+            render_instructions(instructions, snapshot.instruction_count, &mut colors, render_options, pic_buffer, pri_buffer, &mut None, |_, _, _, _| ())?;
+
+            finish_render(instruction_index, render_options, pic_buffer, pri_buffer);
+
+            Ok(())
+        })
+    }
+}
+
+fn draw_pixel_lines(lines : &[PosU8], pic_buffer : &mut PixelBuffer, pic_color : Option<u8>, pri_buffer : &mut PixelBuffer, pri_color : Option<u8>, instruction_index : usize) -> Result<(), AgiError> {
+    let mut set_buffers_pixels = |x : usize, y : usize, sub_index : usize| -> Result<(), AgiError> {
+        pic_buffer.set_pixel(x, y, pic_color, InstructionIndex::new_sub(instruction_index, sub_index))?;
+        pri_buffer.set_pixel(x, y, pri_color, InstructionIndex::new_sub(instruction_index, sub_index))?;
+
+        Ok(())
+    };
+
+    if lines.len() == 1 {
+        // Just draw a single pixel
+        set_buffers_pixels(lines[0].x as usize, lines[0].y as usize, 0)?;
+    } else {
+        for (line_index, line) in lines.windows(2).enumerate() {
+            rasterize_line(line[0], line[1], |x, y| set_buffers_pixels(x, y, line_index))?;
+        }
+    }
+
+    Ok(())
+}
+
+/*
+Walks the pixels of a line the way Sierra's interpreter does.  The original steps one pixel at a time along the longer
+axis, accumulating a fractional step on the shorter one and rounding it with a slight bias away from the direction of
+travel (< 0.499 rounds down going forwards, <= 0.501 rounds down going backwards).  The minor axis offsets have a
+denominator of at most 167 and so can never land between 0.499 and 0.501, which means that rounding is exactly
+"round half away from the start point", and can be done on integers without accumulating any error.
+*/
+pub fn rasterize_line(start : PosU8, end : PosU8, mut plot : impl FnMut(usize, usize) -> Result<(), AgiError>) -> Result<(), AgiError> {
+    let (x1, y1, x2, y2) = (start.x as i32, start.y as i32, end.x as i32, end.y as i32);
+    let (width, height) = (x2 - x1, y2 - y1);
+
+    // Ties go to the y axis, like the original
+    let (major, minor) = if width.abs() > height.abs() { (width.abs(), height.abs()) } else { (height.abs(), width.abs()) };
+
+    for step in 0..=major {
+        let minor_step = if major == 0 { 0 } else { (2 * step * minor + major) / (2 * major) };
+
+        let (x, y) = if width.abs() > height.abs() {
+            (x1 + step * width.signum(), y1 + minor_step * height.signum())
+        } else {
+            (x1 + minor_step * width.signum(), y1 + step * height.signum())
+        };
+
+        plot(x as usize, y as usize)?;
+    }
+
+    Ok(())
+}
+
+// The edges touched by each of the seed points of a fill instruction
+type FillEdgeLists = Vec<Vec<FillEdge>>;
+
+fn pixel_fill(points : &[PosU8], pic_buffer : &mut PixelBuffer, pic_color : Option<u8>, pri_buffer : &mut PixelBuffer, pri_color : Option<u8>, instruction_index : usize, track_edges : bool) -> Result<FillEdgeLists, AgiError> {
+    let mut pic_edges : FillEdgeLists = vec![];
+
+    for (sub_index, point) in points.iter().enumerate() {
+        pic_edges.push(pixel_fill_from_point(*point, pic_buffer, pic_color, pri_buffer, pri_color, InstructionIndex::new_sub(instruction_index, sub_index), track_edges)?);
+    }
+
+    Ok(pic_edges)
+}
+
+/*
+This mirrors the interpreter's fill check, which decides what is fillable based on which of the screens are enabled:
+ - Picture drawing on (priority either way): spread over white picture pixels, unless we're filling with white
+ - Only priority drawing on: spread over red priority pixels, unless we're filling with red
+ - Both off: nothing is filled
+Note that when both are on, only the picture screen is consulted, even though both get written.
+*/
+fn pixel_fill_from_point(point : PosU8, pic_buffer : &mut PixelBuffer, pic_color : Option<u8>, pri_buffer : &mut PixelBuffer, pri_color : Option<u8>, instruction_index : InstructionIndex, track_edges : bool) -> Result<Vec<FillEdge>, AgiError> {
+    // Use these to track what is touched by the fill
+    let mut fill_edges : Vec<FillEdge> = vec![];
+
+    let (test_buffer_is_pic, base_color) = match (pic_color, pri_color) {
+        (Some(pic_color), _) if pic_color != PIC_BUFFER_BASE_COLOR => (true, PIC_BUFFER_BASE_COLOR),
+        (None, Some(pri_color)) if pri_color != PRI_BUFFER_BASE_COLOR => (false, PRI_BUFFER_BASE_COLOR),
+        _ => return Ok(fill_edges)
+    };
+
+    // Like the interpreter, seeds outside the picture just don't fill anything
+    let (x, y) = (point.x as usize, point.y as usize);
+    if x >= VIEWPORT_WIDTH || y >= VIEWPORT_HEIGHT {
+        return Ok(fill_edges);
+    }
+
+    let (mut top, mut right, mut bottom, mut left) = (false, false, false, false);
+
+    let mut seen_lines : HashSet<InstructionIndex> = HashSet::new();
+    let mut add_edge = |pic_buffer : &PixelBuffer, index : usize| {
+        if !track_edges {
+            return;
+        }
+
+        if let Some(inst) = pic_buffer.instruction_indexes[index] {
+            // Big fills can be bordered by hundreds of line segments, so a linear search of the edges gets slow
+            if inst.base_index != instruction_index.base_index && seen_lines.insert(inst) {
+                fill_edges.push(FillEdge::Line(inst));
+            }
+        }
+    };
+
+    // A span based scanline fill: pop a seed, extend it left and right as far as it goes, paint the whole span, then
+    // push one seed for each run of fillable pixels directly above and below it.  This touches the same pixels as the
+    // interpreter's fill, but tests each of them far fewer times.
+    let seed = y * VIEWPORT_WIDTH + x;
+    let test_pixels = if test_buffer_is_pic { &pic_buffer.pixels } else { &pri_buffer.pixels };
+    if test_pixels[seed] != base_color {
+        add_edge(pic_buffer, seed);
+        return Ok(fill_edges);
+    }
+
+    let mut fill_stack : Vec<usize> = vec![seed];
+
+    while let Some(seed) = fill_stack.pop() {
+        let test_pixels = if test_buffer_is_pic { &pic_buffer.pixels } else { &pri_buffer.pixels };
+
+        if test_pixels[seed] != base_color {
+            // Already painted by an earlier span
+            continue;
+        }
+
+        let row_start = seed - seed % VIEWPORT_WIDTH;
+        let row_end = row_start + VIEWPORT_WIDTH;
+        let y = seed / VIEWPORT_WIDTH;
+
+        let mut span_start = seed;
+        while span_start > row_start && test_pixels[span_start - 1] == base_color {
+            span_start -= 1;
+        }
+
+        let mut span_end = seed + 1;
+        while span_end < row_end && test_pixels[span_end] == base_color {
+            span_end += 1;
+        }
+
+        // Paint the span into whichever buffers are enabled
+        if let Some(pic_color) = pic_color {
+            pic_buffer.pixels[span_start..span_end].fill(pic_color);
+            pic_buffer.instruction_indexes[span_start..span_end].fill(Some(instruction_index));
+        }
+
+        if let Some(pri_color) = pri_color {
+            pri_buffer.pixels[span_start..span_end].fill(pri_color);
+            pri_buffer.instruction_indexes[span_start..span_end].fill(Some(instruction_index));
+        }
+
+        // Track what stopped the span on either side
+        if span_start == row_start {
+            left = true;
+        } else {
+            add_edge(pic_buffer, span_start - 1);
+        }
+
+        if span_end == row_end {
+            right = true;
+        } else {
+            add_edge(pic_buffer, span_end);
+        }
+
+        // Seed the rows above and below
+        let test_pixels = if test_buffer_is_pic { &pic_buffer.pixels } else { &pri_buffer.pixels };
+        for (at_border, offset_row) in [(y == 0, span_start.wrapping_sub(VIEWPORT_WIDTH)), (y == VIEWPORT_HEIGHT - 1, span_start + VIEWPORT_WIDTH)] {
+            if at_border {
+                continue;
+            }
+
+            let mut in_run = false;
+            for index in offset_row..offset_row + (span_end - span_start) {
+                if test_pixels[index] == base_color {
+                    if !in_run {
+                        fill_stack.push(index);
+                        in_run = true;
+                    }
+                } else {
+                    in_run = false;
+                    add_edge(pic_buffer, index);
+                }
+            }
+        }
+
+        top |= y == 0;
+        bottom |= y == VIEWPORT_HEIGHT - 1;
+    }
+
+    if top {
+        fill_edges.push(FillEdge::TopBorder);
+    }
+    if right {
+        fill_edges.push(FillEdge::RightBorder);
+    }
+    if bottom {
+        fill_edges.push(FillEdge::BottomBorder);
+    }
+    if left {
+        fill_edges.push(FillEdge::LeftBorder)
+    }
+
+    Ok(fill_edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::{Path, PathBuf}};
+    use super::*;
+
+    const REFERENCE_RENDERS_FILE : &str = "reference_renders.txt";
+
+    fn sample_pics_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")
+    }
+
+    fn render_raw(raw_data : &[u8]) -> (PixelBuffer, PixelBuffer) {
+        let pic = PicResource::new(raw_data).unwrap();
+        render_pic(&pic)
+    }
+
+    fn render_pic(pic : &PicResource) -> (PixelBuffer, PixelBuffer) {
+        let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+        render_to_buffers(pic.get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut None).unwrap();
+        (pic_buffer, pri_buffer)
+    }
+
+    // FNV-1a over the colour index of each pixel, the same as the reference renderer in tests/reference_renders.rs
+    fn buffer_hash(buffer : &PixelBuffer) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for color in buffer.get_pixel_indexes() {
+            hash ^= *color as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    #[test]
+    fn fill_with_only_priority_enabled_checks_priority_buffer() {
+        // Priority line down x=10, then a priority only fill to its left
+        let (pic_buffer, pri_buffer) = render_raw(&[0xF1, 0xF2, 0x01, 0xF6, 10, 0, 10, 167, 0xF2, 0x03, 0xF8, 5, 5, 0xFF]);
+
+        assert_eq!(pri_buffer.get_pixel(5, 5).unwrap(), get_color(0x03));
+        assert_eq!(pri_buffer.get_pixel(10, 5).unwrap(), get_color(0x01));
+        assert_eq!(pri_buffer.get_pixel(20, 5).unwrap(), get_color(PRI_BUFFER_BASE_COLOR));
+        assert_eq!(pic_buffer.get_pixel(5, 5).unwrap(), get_color(PIC_BUFFER_BASE_COLOR));
+    }
+
+    #[test]
+    fn fill_with_picture_enabled_ignores_priority_lines() {
+        // Same priority line, but the fill has picture drawing on so it only looks at the (empty) picture buffer
+        let (pic_buffer, pri_buffer) = render_raw(&[0xF1, 0xF2, 0x01, 0xF6, 10, 0, 10, 167, 0xF0, 0x02, 0xF2, 0x03, 0xF8, 5, 5, 0xFF]);
+
+        assert_eq!(pic_buffer.get_pixel(20, 5).unwrap(), get_color(0x02));
+        assert_eq!(pri_buffer.get_pixel(10, 5).unwrap(), get_color(0x03));
+        assert_eq!(pri_buffer.get_pixel(20, 5).unwrap(), get_color(0x03));
+    }
+
+    #[test]
+    fn fill_with_white_picture_color_fills_nothing() {
+        // Even with a priority color set, filling with white never passes the fill check
+        let (pic_buffer, pri_buffer) = render_raw(&[0xF0, 0x0F, 0xF2, 0x03, 0xF8, 5, 5, 0xFF]);
+
+        assert_eq!(pic_buffer.get_pixel(5, 5).unwrap(), get_color(PIC_BUFFER_BASE_COLOR));
+        assert_eq!(pri_buffer.get_pixel(5, 5).unwrap(), get_color(PRI_BUFFER_BASE_COLOR));
+    }
+
+    #[test]
+    fn fill_stops_at_picture_lines() {
+        // A box, filled from inside, should leave everything outside untouched
+        let (pic_buffer, _) = render_raw(&[0xF0, 0x00, 0xF6, 10, 10, 20, 10, 20, 20, 10, 20, 10, 10, 0xF0, 0x01, 0xF8, 15, 15, 0xFF]);
+
+        assert_eq!(pic_buffer.get_pixel(15, 15).unwrap(), get_color(0x01));
+        assert_eq!(pic_buffer.get_pixel(10, 15).unwrap(), get_color(0x00));
+        assert_eq!(pic_buffer.get_pixel(5, 15).unwrap(), get_color(PIC_BUFFER_BASE_COLOR));
+        assert_eq!(pic_buffer.get_pixel(25, 25).unwrap(), get_color(PIC_BUFFER_BASE_COLOR));
+    }
+
+    fn render_fills(raw_data : &[u8]) -> Vec<VectorFill> {
+        let pic = PicResource::new(raw_data).unwrap();
+        let mut pic_vectors = ShapeBuffer::new();
+        render_to_buffers(pic.get_instructions(), &RenderOptions::default(), &mut None, &mut None, &mut Some(&mut pic_vectors)).unwrap();
+        pic_vectors.fills
+    }
+
+    fn triangle_area(fill : &VectorFill) -> f32 {
+        fill.triangles.chunks(3).map(|t| {
+            let (a, b, c) = (fill.vertices[t[0]], fill.vertices[t[1]], fill.vertices[t[2]]);
+            ((b - a).x * (c - a).y - (b - a).y * (c - a).x).abs() / 2.0
+        })
+        .sum()
+    }
+
+    #[test]
+    fn vector_fill_meets_bounding_lines() {
+        let fills = render_fills(&[0xF0, 0x00, 0xF6, 10, 10, 20, 10, 20, 20, 10, 20, 10, 10, 0xF0, 0x01, 0xF8, 15, 15, 0xFF]);
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].agi_color, 0x01);
+        assert_eq!(triangle_area(&fills[0]), 100.0);
+        assert_eq!(fills[0].outlines, vec![vec![pos2(10.0, 10.0), pos2(20.0, 10.0), pos2(20.0, 20.0), pos2(10.0, 20.0)]]);
+    }
+
+    #[test]
+    fn vector_fill_has_holes() {
+        // A smaller box inside the first one, which the fill has to go around
+        let fills = render_fills(&[0xF0, 0x00, 0xF6, 10, 10, 20, 10, 20, 20, 10, 20, 10, 10, 0xF6, 13, 13, 17, 13, 17, 17, 13, 17, 13, 13, 0xF0, 0x01, 0xF8, 11, 11, 0xFF]);
+
+        // The hole runs through the inner box's pixel centers, but its corners are cut off diagonally, since the corner
+        // pixels of the inner box only touch the unfilled inside of the box diagonally
+        assert_eq!(fills.len(), 1);
+        assert_eq!(triangle_area(&fills[0]), 100.0 - 16.0 + 2.0);
+        assert_eq!(fills[0].outlines.len(), 2);
+        assert!(fills[0].outlines.iter().any(|o| o.len() == 8 && o.iter().all(|p| (13.0..=17.0).contains(&p.x) && (13.0..=17.0).contains(&p.y))));
+    }
+
+    #[test]
+    fn empty_pictures_render() {
+        let (pic_buffer, pri_buffer) = render_raw(&[]);
+        assert_eq!(pic_buffer.get_pixel_indexes(), PixelBuffer::new(PIC_BUFFER_BASE_COLOR).get_pixel_indexes());
+        assert_eq!(pri_buffer.get_pixel_indexes(), PixelBuffer::new(PRI_BUFFER_BASE_COLOR).get_pixel_indexes());
+
+        let render_options = RenderOptions { render_only_selected_instruction : true, show_fill_outlines : true, last_instruction_sub_items : Some(1) };
+        render_to_buffers(&[], &render_options, &mut None, &mut None, &mut Some(&mut ShapeBuffer::new())).unwrap();
+    }
+
+    #[test]
+    fn pixels_record_the_line_that_drew_them() {
+        // An absolute line through three points, so two segments, and nothing drawn anywhere else
+        let (pic_buffer, _) = render_raw(&[0xF0, 0x01, 0xF6, 10, 10, 20, 10, 20, 20, 0xFF]);
+
+        assert_eq!(pic_buffer.get_pixel_instruction(15, 10).unwrap(), Some(InstructionIndex::new_sub(1, 0)));
+        assert_eq!(pic_buffer.get_pixel_instruction(20, 15).unwrap(), Some(InstructionIndex::new_sub(1, 1)));
+        assert_eq!(pic_buffer.get_pixel_instruction(0, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn colors_use_the_low_4_bits() {
+        // 0x21 is dark blue on the picture screen, and 0xE4 red on the priority screen
+        let (pic_buffer, pri_buffer) = render_raw(&[0xF0, 0x21, 0xF2, 0xE4, 0xF6, 10, 10, 20, 10, 0xFF]);
+        assert_eq!(pic_buffer.get_pixel_index(15, 10).unwrap(), 1);
+        assert_eq!(pri_buffer.get_pixel_index(15, 10).unwrap(), 4);
+    }
+
+    #[test]
+    fn off_screen_coordinates_are_drawn_on_the_edge() {
+        // A line from (200,10) to (200,200) ends up down the right edge and the corner, and nothing wraps onto x 0
+        let (pic_buffer, _) = render_raw(&[0xF0, 0x01, 0xF6, 200, 10, 200, 200, 0xEF, 0xEF, 0xFF]);
+        assert_eq!(pic_buffer.get_pixel_index(VIEWPORT_WIDTH - 1, 10).unwrap(), 1);
+        assert_eq!(pic_buffer.get_pixel_index(VIEWPORT_WIDTH - 1, VIEWPORT_HEIGHT - 1).unwrap(), 1);
+        assert_eq!(pic_buffer.get_pixel_index(0, 11).unwrap(), PIC_BUFFER_BASE_COLOR);
+        assert_eq!(pic_buffer.get_pixel_indexes().iter().filter(|color| **color == 1).count(), VIEWPORT_HEIGHT - 10);
+
+        let mut buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        assert!(buffer.set_pixel(VIEWPORT_WIDTH, 0, Some(1), InstructionIndex::default()).is_err());
+        assert!(buffer.set_pixel(0, VIEWPORT_HEIGHT, Some(1), InstructionIndex::default()).is_err());
+        assert!(buffer.get_pixel_index(VIEWPORT_WIDTH, 0).is_err());
+        assert!(!buffer.get_pixel_indexes().contains(&1));
+    }
+
+    #[test]
+    fn differences_are_found_pixel_by_pixel() {
+        // The same line, one a pixel lower than the other
+        let (first, _) = render_raw(&[0xF0, 0x01, 0xF6, 10, 10, 20, 10, 0xFF]);
+        let (second, _) = render_raw(&[0xF0, 0x01, 0xF6, 10, 11, 20, 11, 0xFF]);
+
+        let differences = first.get_differences(&second);
+        assert_eq!(differences.len(), VIEWPORT_PIXELS);
+        assert_eq!(differences.iter().filter(|different| **different).count(), 22);
+        assert!(differences[10 * VIEWPORT_WIDTH + 15] && differences[11 * VIEWPORT_WIDTH + 15]);
+        assert!(!first.get_differences(&first).contains(&true));
+    }
+
+    #[test]
+    fn last_instruction_can_be_drawn_in_parts() {
+        // The same two segment line as above, drawn up to the end of its first segment
+        let pic = PicResource::new(&[0xF0, 0x01, 0xF6, 10, 10, 20, 10, 20, 20, 0xFF]).unwrap();
+        let instructions = &pic.get_instructions()[0..=1];
+        assert_eq!(instructions[1].get_sub_item_count(), 2);
+
+        let render_options = RenderOptions { last_instruction_sub_items : Some(1), ..Default::default() };
+        let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        render_to_buffers(instructions, &render_options, &mut Some(&mut pic_buffer), &mut None, &mut None).unwrap();
+
+        assert_eq!(pic_buffer.get_pixel_instruction(15, 10).unwrap(), Some(InstructionIndex::new_sub(1, 0)));
+        assert_eq!(pic_buffer.get_pixel_instruction(20, 15).unwrap(), None);
+
+        // The snapshot of the whole instruction can't be used, so it has to come out the same
+        let snapshots = RenderSnapshots::new(instructions, 1).unwrap();
+        let mut snapshot_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        snapshots.render_after_instruction(instructions, 1, &render_options, &mut Some(&mut snapshot_buffer), &mut None, &mut None).unwrap();
+        assert_eq!(snapshot_buffer.instruction_indexes, pic_buffer.instruction_indexes);
+    }
+
+    #[test]
+    fn only_the_selected_part_is_isolated() {
+        // Both segments drawn, but only the second one shown
+        let pic = PicResource::new(&[0xF0, 0x01, 0xF6, 10, 10, 20, 10, 20, 20, 0xFF]).unwrap();
+        let render_options = RenderOptions { render_only_selected_instruction : true, last_instruction_sub_items : Some(2), ..Default::default() };
+        let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        render_to_buffers(&pic.get_instructions()[0..=1], &render_options, &mut Some(&mut pic_buffer), &mut None, &mut None).unwrap();
+
+        assert_eq!(pic_buffer.get_pixel_index(15, 10).unwrap(), PIC_BUFFER_BASE_COLOR);
+        assert_eq!(pic_buffer.get_pixel_index(20, 15).unwrap(), 0x01);
+    }
+
+    #[test]
+    fn vector_fill_stops_at_viewport_border() {
+        // Nothing to stop the fill, so it should cover exactly the picture area (measured between pixel centers)
+        let fills = render_fills(&[0xF0, 0x01, 0xF8, 50, 50, 0xFF]);
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(triangle_area(&fills[0]), ((VIEWPORT_WIDTH - 1) * (VIEWPORT_HEIGHT - 1)) as f32);
+    }
+
+    #[test]
+    fn sample_pics_match_reference_renders() {
+        // The references come from a separate renderer, see the file's header
+        let reference = fs::read_to_string(sample_pics_dir().join(REFERENCE_RENDERS_FILE)).unwrap();
+        let expected : Vec<&str> = reference.lines().filter(|line| !line.starts_with('#')).collect();
+
+        let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+        let actual : Vec<String> = game.pic_resources.iter().zip(game.all_resources.iter()).map(|(pic, resource)| {
+            let (pic_buffer, pri_buffer) = render_pic(pic);
+            format!("{} {:016x} {:016x}", resource.get_resource_index(), buffer_hash(&pic_buffer), buffer_hash(&pri_buffer))
+        })
+        .collect();
+
+        assert_eq!(expected.len(), actual.len());
+        for (expected, actual) in expected.iter().zip(actual.iter()) {
+            assert_eq!(expected, actual, "Render of sample PIC differs from the reference (resource, picture hash, priority hash)");
+        }
+    }
+
+    // The original line algorithm from the AGI specs, stepping fractionally along the minor axis and applying Sierra's
+    // rounding, but using exact rational arithmetic (numerator over the major axis length) so nothing can drift
+    fn reference_line(x1 : i32, y1 : i32, x2 : i32, y2 : i32) -> Vec<(usize, usize)> {
+        fn sierra_round_exact(numerator : i32, denominator : i32, dir : i32) -> usize {
+            let floor = numerator.div_euclid(denominator);
+            let frac = numerator.rem_euclid(denominator);
+            let round_down = if dir < 0 { frac * 1000 <= 501 * denominator } else { frac * 1000 < 499 * denominator };
+            (if round_down || frac == 0 { floor } else { floor + 1 }) as usize
+        }
+
+        let (width, height) = (x2 - x1, y2 - y1);
+        let mut pixels = vec![];
+
+        if width.abs() > height.abs() {
+            let denominator = width.abs();
+            let (mut x, mut y) = (x1, y1 * denominator);
+            while x != x2 {
+                pixels.push((x as usize, sierra_round_exact(y, denominator, height)));
+                x += width.signum();
+                y += height;
+            }
+        } else if height != 0 {
+            let denominator = height.abs();
+            let (mut x, mut y) = (x1 * denominator, y1);
+            while y != y2 {
+                pixels.push((sierra_round_exact(x, denominator, width), y as usize));
+                x += width;
+                y += height.signum();
+            }
+        }
+        pixels.push((x2 as usize, y2 as usize));
+
+        pixels
+    }
+
+    #[test]
+    fn lines_match_reference_for_every_offset() {
+        // Both implementations only depend on the offset between the two points (moving a line moves all of its pixels
+        // by the same amount), so checking every possible offset covers every line in the picture
+        let (max_x, max_y) = (VIEWPORT_WIDTH as i32 - 1, VIEWPORT_HEIGHT as i32 - 1);
+
+        for width in -max_x..=max_x {
+            for height in -max_y..=max_y {
+                let (x1, y1) = (if width < 0 { max_x } else { 0 }, if height < 0 { max_y } else { 0 });
+                let (x2, y2) = (x1 + width, y1 + height);
+
+                let mut pixels = vec![];
+                rasterize_line(PosU8::new(x1 as u8, y1 as u8), PosU8::new(x2 as u8, y2 as u8), |x, y| {
+                    pixels.push((x, y));
+                    Ok(())
+                }).unwrap();
+
+                assert_eq!(pixels, reference_line(x1, y1, x2, y2), "Line from ({x1},{y1}) to ({x2},{y2}) differs");
+            }
+        }
+    }
+
+    #[test]
+    fn lines_are_translation_invariant() {
+        // Backs up the assumption above by moving a few awkward lines to every position they fit at
+        for (width, height) in [(159, 3), (-3, 167), (7, -5), (-100, -99), (1, 1), (0, -20)] {
+            let reference = reference_line(0, 0, width, height);
+            for x1 in 0.max(-width)..(VIEWPORT_WIDTH as i32).min(VIEWPORT_WIDTH as i32 - width) {
+                for y1 in 0.max(-height)..(VIEWPORT_HEIGHT as i32).min(VIEWPORT_HEIGHT as i32 - height) {
+                    let mut pixels = vec![];
+                    rasterize_line(PosU8::new(x1 as u8, y1 as u8), PosU8::new((x1 + width) as u8, (y1 + height) as u8), |x, y| {
+                        pixels.push((x as i32 - x1, y as i32 - y1));
+                        Ok(())
+                    }).unwrap();
+
+                    let expected : Vec<(i32, i32)> = reference.iter().map(|(x, y)| (*x as i32, *y as i32)).collect();
+                    assert_eq!(pixels, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn snapshots_match_full_renders() {
+        let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+
+        // Colour arguments above 15 have to come back the same from a snapshot as from a full render, so add a picture
+        // that draws and fills with them, long enough to span a few snapshots
+        let mut raw_data = vec![];
+        for i in 0..24u8 {
+            raw_data.extend([0xF0, 0x10 + i * 9, 0xF2, 0xE0 - i * 3, 0xF6, i * 6, 0, 159 - i * 6, 167, 0xF8, i * 6 + 1, 1]);
+        }
+        raw_data.push(0xFF);
+        let high_colors = PicResource::new(&raw_data).unwrap();
+
+        // Every full render replays the whole picture so this gets slow quickly, a couple of short pictures is plenty
+        for pic in [&game.pic_resources[13], &game.pic_resources[31], &high_colors] {
+            let instructions = pic.get_instructions();
+            let snapshots = RenderSnapshots::new(instructions, 16).unwrap();
+
+            for render_only_selected_instruction in [false, true] {
+                let render_options = RenderOptions { render_only_selected_instruction, ..Default::default() };
+
+                for instruction_index in 0..instructions.len() {
+                    let (mut expected_pic, mut expected_pri) = (PixelBuffer::new(PIC_BUFFER_BASE_COLOR), PixelBuffer::new(PRI_BUFFER_BASE_COLOR));
+                    render_to_buffers(&instructions[0..=instruction_index], &render_options, &mut Some(&mut expected_pic), &mut Some(&mut expected_pri), &mut None).unwrap();
+
+                    let (mut actual_pic, mut actual_pri) = (PixelBuffer::new(PIC_BUFFER_BASE_COLOR), PixelBuffer::new(PRI_BUFFER_BASE_COLOR));
+                    snapshots.render_after_instruction(instructions, instruction_index, &render_options, &mut Some(&mut actual_pic), &mut Some(&mut actual_pri), &mut None).unwrap();
+
+                    for (expected, actual) in [(&expected_pic, &actual_pic), (&expected_pri, &actual_pri)] {
+                        assert_eq!(expected.get_pixel_indexes(), actual.get_pixel_indexes(), "Pixels differ after instruction {instruction_index}");
+                        assert_eq!(expected.instruction_indexes, actual.instruction_indexes, "Instruction indexes differ after instruction {instruction_index}");
+                    }
+                }
+            }
+        }
+    }
+}
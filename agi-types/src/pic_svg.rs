@@ -0,0 +1,168 @@
+use std::fmt::Write;
+use egui::{Color32, Pos2};
+use crate::*;
+
+pub struct SvgOptions {
+    pub scale : f32, // SVG units per AGI pixel vertically
+    pub aspect_ratio : f32, // Width of an AGI pixel relative to its height, 2.0 on the original hardware
+    pub line_width : f32, // In AGI pixels, so it scales along with the picture
+    pub palette : Palette
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self { scale : 4.0, aspect_ratio : 2.0, line_width : 1.0, palette : Palette::EGA }
+    }
+}
+
+/*
+Exports the vector interpretation of a picture, the same shapes the viewer draws in its upscaled view, as an SVG
+document.  Every instruction that draws something gets its own group, with an id built from its index and its text in
+a data attribute, so individual strokes are easy to find and pick apart in an editor.
+
+The fills go in a layer underneath all the lines, since the fill polygons run up to the middle of the lines that bound
+them, and drawing them in instruction order would cover half of each line.  So a fill instruction's group is in the
+"fills" layer and a line instruction's group is in the "lines" layer.
+*/
+pub fn instructions_to_svg(instructions : &[DerivedPicRenderInstruction], options : &SvgOptions) -> Result<String, AgiError> {
+    let mut shapes = ShapeBuffer::new();
+    render_to_buffers(instructions, &RenderOptions::default(), &mut None, &mut None, &mut Some(&mut shapes))?;
+
+    let x_scale = options.scale * options.aspect_ratio;
+    let y_scale = options.scale;
+    let to_svg = |p : &Pos2| (format_number((p.x + 0.5) * x_scale), format_number((p.y + 0.5) * y_scale));
+
+    let (width, height) = (format_number(VIEWPORT_WIDTH as f32 * x_scale), format_number(VIEWPORT_HEIGHT as f32 * y_scale));
+
+    // Writing to a String can't fail, so the fmt::Results are ignored throughout
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#);
+    let _ = writeln!(svg, r#"  <rect id="background" width="{width}" height="{height}" fill="{}"/>"#, svg_color(options.palette.get_color(PIC_BUFFER_BASE_COLOR)));
+
+    // Fills, each seed point being a separate polygon with its holes cut out
+    let _ = writeln!(svg, r#"  <g id="fills" stroke="none" fill-rule="evenodd">"#);
+    for (instruction_index, fills) in group_by_instruction(shapes.get_fills(), |f| f.instruction_index) {
+        let _ = writeln!(svg, "{}", group_start("fill", instruction_index, instructions));
+        for fill in fills {
+            let mut path_data = String::new();
+            for outline in fill.outlines.iter() {
+                for (i, p) in outline.iter().enumerate() {
+                    let (x, y) = to_svg(p);
+                    let _ = write!(path_data, "{}{},{} ", if i == 0 { "M" } else { "L" }, x, y);
+                }
+                path_data.push('Z');
+            }
+            let _ = writeln!(svg, r#"      <path d="{}" fill="{}"/>"#, path_data, svg_color(options.palette.get_color(fill.agi_color)));
+        }
+        let _ = writeln!(svg, "    </g>");
+    }
+    let _ = writeln!(svg, "  </g>");
+
+    // Lines, with single points drawn as dots the width of a line
+    let line_width = options.line_width * options.scale;
+    let _ = writeln!(svg, r#"  <g id="lines" fill="none" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round">"#, format_number(line_width));
+    for (instruction_index, paths) in group_by_instruction(shapes.get_paths(), |p| p.instruction_index) {
+        let _ = writeln!(svg, "{}", group_start("line", instruction_index, instructions));
+        for path in paths {
+            let color = svg_color(options.palette.get_color(path.agi_color));
+            if path.points.len() == 1 {
+                let (x, y) = to_svg(&path.points[0]);
+                let _ = writeln!(svg, r#"      <circle cx="{}" cy="{}" r="{}" fill="{}"/>"#, x, y, format_number(line_width / 2.0), color);
+            } else {
+                let points = path.points.iter().map(|p| {
+                    let (x, y) = to_svg(p);
+                    format!("{},{}", x, y)
+                }).collect::<Vec<String>>().join(" ");
+                let _ = writeln!(svg, r#"      <polyline points="{}" stroke="{}"/>"#, points, color);
+            }
+        }
+        let _ = writeln!(svg, "    </g>");
+    }
+    let _ = writeln!(svg, "  </g>");
+    let _ = writeln!(svg, "</svg>");
+
+    Ok(svg)
+}
+
+// Splits shapes into runs that came from the same instruction, which they're already sorted by since they're added
+// as the instructions are rendered
+fn group_by_instruction<T>(shapes : &[T], instruction_index : impl Fn(&T) -> usize) -> Vec<(usize, &[T])> {
+    let mut groups = vec![];
+    let mut start = 0;
+    for i in 1..=shapes.len() {
+        if i == shapes.len() || instruction_index(&shapes[i]) != instruction_index(&shapes[start]) {
+            groups.push((instruction_index(&shapes[start]), &shapes[start..i]));
+            start = i;
+        }
+    }
+    groups
+}
+
+fn group_start(kind : &str, instruction_index : usize, instructions : &[DerivedPicRenderInstruction]) -> String {
+    format!(r#"    <g id="{}-{}" data-instruction-index="{}" data-instruction="{}">"#,
+        kind, instruction_index, instruction_index, escape_attribute(&instructions[instruction_index].to_string()))
+}
+
+fn svg_color(color : Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+// Keeps the output readable, three decimal places is far more than any viewer can show at sensible scales
+fn format_number(value : f32) -> String {
+    let formatted = format!("{:.3}", value);
+    match formatted.trim_end_matches('0').trim_end_matches('.') {
+        "" | "-" | "-0" => String::from("0"),
+        trimmed => trimmed.to_string()
+    }
+}
+
+fn escape_attribute(text : &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A blue box with a green fill inside it
+    const BOXED_FILL : [u8 ; 18] = [0xF0, 0x01, 0xF6, 10, 10, 20, 10, 20, 20, 10, 20, 10, 10, 0xF0, 0x02, 0xF8, 15, 15];
+
+    #[test]
+    fn svg_has_a_group_per_drawing_instruction() {
+        let pic = PicResource::new(&BOXED_FILL).unwrap();
+        let svg = instructions_to_svg(pic.get_instructions(), &SvgOptions::default()).unwrap();
+
+        assert!(svg.contains(r#"<g id="line-1" data-instruction-index="1""#));
+        assert!(svg.contains(r#"<g id="fill-3" data-instruction-index="3""#));
+        // The SetColor instructions don't draw anything, so they don't get groups
+        assert!(!svg.contains(r#"id="line-0""#) && !svg.contains(r#"id="fill-0""#));
+
+        assert!(svg.contains(r##"stroke="#0000aa""##));
+        assert!(svg.contains(r##"fill="#00aa00""##));
+        // Default scale is 4 with the 2:1 aspect ratio, so the box corner at (10,10) lands on the center of that pixel
+        assert!(svg.contains(r#"<polyline points="84,42 164,42 164,82 84,82 84,42""#));
+        assert!(svg.contains(r#"width="1280" height="672""#));
+    }
+
+    #[test]
+    fn svg_uses_the_selected_palette() {
+        let pic = PicResource::new(&BOXED_FILL).unwrap();
+        let options = SvgOptions { palette : Palette::GREYSCALE, ..Default::default() };
+        let svg = instructions_to_svg(pic.get_instructions(), &options).unwrap();
+
+        assert!(svg.contains(&format!(r#"stroke="{}""#, svg_color(Palette::GREYSCALE.get_color(1)))));
+        assert!(svg.contains(&format!(r#"fill="{}""#, svg_color(Palette::GREYSCALE.get_color(2)))));
+        assert!(!svg.contains(r##"#0000aa"##));
+    }
+}
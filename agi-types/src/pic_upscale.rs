@@ -0,0 +1,422 @@
+use egui::Color32;
+use crate::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Upscaler {
+    None,
+    Scale2x,
+    Scale3x,
+    Xbr,
+    Hq2x
+}
+
+// The result of an upscale, either still palette indexes, or colors for the upscalers that blend
+pub enum UpscaledPixels {
+    Indexed(Vec<u8>),
+    Rgb(Vec<Color32>)
+}
+
+pub struct UpscaledImage {
+    pub width : usize,
+    pub height : usize,
+    pub pixels : UpscaledPixels
+}
+
+impl UpscaledImage {
+    pub fn to_colors(&self, palette : &Palette) -> Vec<Color32> {
+        match &self.pixels {
+            UpscaledPixels::Indexed(indexes) => indexes.iter().map(|c| palette.get_color(*c)).collect(),
+            UpscaledPixels::Rgb(colors) => colors.clone()
+        }
+    }
+}
+
+impl Upscaler {
+    pub const ALL : [Upscaler ; 5] = [Upscaler::None, Upscaler::Scale2x, Upscaler::Scale3x, Upscaler::Xbr, Upscaler::Hq2x];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Scale2x => "Scale2x",
+            Self::Scale3x => "Scale3x",
+            Self::Xbr => "2xBR",
+            Self::Hq2x => "hq2x"
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Scale3x => 3,
+            Self::Scale2x | Self::Xbr | Self::Hq2x => 2
+        }
+    }
+
+    // Upscales palette indexes, such as the contents of a PixelBuffer.  The Scale2x family only ever copies existing
+    // pixels, so their output stays as palette indexes, and the palette is only used by the upscalers that blend
+    pub fn upscale(&self, pixels : &[u8], width : usize, height : usize, palette : &Palette) -> UpscaledImage {
+        let (scaled_width, scaled_height) = (width * self.factor(), height * self.factor());
+        let to_colors = || pixels.iter().map(|c| palette.get_color(*c)).collect::<Vec<Color32>>();
+
+        let pixels = match self {
+            Self::None => UpscaledPixels::Indexed(pixels.to_vec()),
+            Self::Scale2x => UpscaledPixels::Indexed(scale2x(pixels, width, height)),
+            Self::Scale3x => UpscaledPixels::Indexed(scale3x(pixels, width, height)),
+            Self::Xbr => UpscaledPixels::Rgb(xbr2x(&to_colors(), width, height)),
+            Self::Hq2x => UpscaledPixels::Rgb(hq2x(&to_colors(), width, height))
+        };
+
+        UpscaledImage { width : scaled_width, height : scaled_height, pixels }
+    }
+}
+
+// Reads pixels around a point, repeating the edge pixels for anything outside the image
+struct Neighbourhood<'a, T> {
+    pixels : &'a [T],
+    width : usize,
+    height : usize
+}
+
+impl<'a, T : Copy> Neighbourhood<'a, T> {
+    fn get(&self, x : usize, y : usize, dx : isize, dy : isize) -> T {
+        let x = (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Runs per_pixel over every source pixel, which returns the factor x factor block of output pixels for it, row by row
+fn upscale_blocks<T : Copy + Default, const N : usize>(pixels : &[T], width : usize, height : usize, factor : usize, per_pixel : impl Fn(&Neighbourhood<T>, usize, usize) -> [T ; N]) -> Vec<T> {
+    let neighbourhood = Neighbourhood { pixels, width, height };
+    let scaled_width = width * factor;
+    let mut scaled = vec![T::default() ; scaled_width * height * factor];
+
+    for y in 0..height {
+        for x in 0..width {
+            let block = per_pixel(&neighbourhood, x, y);
+            for (i, pixel) in block.iter().enumerate() {
+                scaled[(y * factor + i / factor) * scaled_width + x * factor + i % factor] = *pixel;
+            }
+        }
+    }
+
+    scaled
+}
+
+/*
+Scale2x and Scale3x (AKA AdvMAME2x/3x) from https://www.scale2x.it/algorithm, using the names from there:
+
+    A B C
+    D E F
+    G H I
+*/
+fn scale2x<T : Copy + Default + Eq>(pixels : &[T], width : usize, height : usize) -> Vec<T> {
+    upscale_blocks(pixels, width, height, 2, |n, x, y| {
+        let (b, d, e, f, h) = (n.get(x, y, 0, -1), n.get(x, y, -1, 0), n.get(x, y, 0, 0), n.get(x, y, 1, 0), n.get(x, y, 0, 1));
+
+        if b != h && d != f {
+            [
+                if d == b { d } else { e }, if b == f { f } else { e },
+                if d == h { d } else { e }, if h == f { f } else { e }
+            ]
+        } else {
+            [e ; 4]
+        }
+    })
+}
+
+fn scale3x<T : Copy + Default + Eq>(pixels : &[T], width : usize, height : usize) -> Vec<T> {
+    upscale_blocks(pixels, width, height, 3, |n, x, y| {
+        let (a, b, c) = (n.get(x, y, -1, -1), n.get(x, y, 0, -1), n.get(x, y, 1, -1));
+        let (d, e, f) = (n.get(x, y, -1, 0), n.get(x, y, 0, 0), n.get(x, y, 1, 0));
+        let (g, h, i) = (n.get(x, y, -1, 1), n.get(x, y, 0, 1), n.get(x, y, 1, 1));
+
+        if b != h && d != f {
+            [
+                if d == b { d } else { e },
+                if (d == b && e != c) || (b == f && e != a) { b } else { e },
+                if b == f { f } else { e },
+                if (d == b && e != g) || (d == h && e != a) { d } else { e },
+                e,
+                if (b == f && e != i) || (h == f && e != c) { f } else { e },
+                if d == h { d } else { e },
+                if (d == h && e != i) || (h == f && e != g) { h } else { e },
+                if h == f { f } else { e }
+            ]
+        } else {
+            [e ; 9]
+        }
+    })
+}
+
+// The luma and chroma that xBR and hqx compare colors in, on a 0-255 scale and rounded the same way as the tables in
+// FFmpeg's xbr and hqx filters
+fn yuv(color : Color32) -> [i32 ; 3] {
+    let (r, g, b) = (color.r() as i32, color.g() as i32, color.b() as i32);
+    let (rg, bg) = (r - g, b - g);
+    [
+        (299 * r + 587 * g + 114 * b) / 1000,
+        (-169 * rg + 500 * bg) / 1000 + 128,
+        (500 * rg - 81 * bg) / 1000 + 128
+    ]
+}
+
+// Weighs up colors channel by channel, the weights adding up to 1 << shift
+fn interpolate(colors : &[(Color32, u32)], shift : u32) -> Color32 {
+    let channel = |get : fn(&Color32) -> u8| -> u8 {
+        (colors.iter().map(|(c, weight)| get(c) as u32 * weight).sum::<u32>() >> shift) as u8
+    };
+    Color32::from_rgb(channel(Color32::r), channel(Color32::g), channel(Color32::b))
+}
+
+/*
+Hyllian's 2xBR, as in FFmpeg's xbr filter, using the names from there for the neighbourhood of E, set out for its
+bottom right corner:
+
+          A1 B1 C1
+       A0 A  B  C  C4
+       D0 D  E  F  F4
+       G0 G  H  I  I4
+          G5 H5 I5
+
+The other corners are done in turn by turning the neighbourhood a quarter at a time.  For each of them, the colour
+distances along the edge running across the corner (through H and F) are weighed against those along the other
+diagonal (through E and I).  Where the edge is across the corner, the corner is blended towards the closer of F and
+H, and shallower edges also blend a little into the output pixel to its left or above it.
+*/
+fn xbr2x(pixels : &[Color32], width : usize, height : usize) -> Vec<Color32> {
+    let yuv_pixels : Vec<[i32 ; 3]> = pixels.iter().map(|c| yuv(*c)).collect();
+    let yuv_neighbourhood = Neighbourhood { pixels : &yuv_pixels, width, height };
+
+    // Moves multiplier / (1 << shift) of the way from color to other, and the even blend of the two the way xBR does it
+    let blend_towards = |color : Color32, other : Color32, multiplier : i32, shift : u32| {
+        let channel = |get : fn(&Color32) -> u8| (get(&color) as i32 + (((get(&other) as i32 - get(&color) as i32) * multiplier) >> shift)) as u8;
+        Color32::from_rgb(channel(Color32::r), channel(Color32::g), channel(Color32::b))
+    };
+    let blend_half = |color : Color32, other : Color32| {
+        Color32::from_rgb((color.r() >> 1) + (other.r() >> 1), (color.g() >> 1) + (other.g() >> 1), (color.b() >> 1) + (other.b() >> 1))
+    };
+
+    upscale_blocks(pixels, width, height, 2, |n, x, y| {
+        let mut block = [n.get(x, y, 0, 0) ; 4];
+
+        for turn in 0..4 {
+            let rotate = |dx : isize, dy : isize| match turn {
+                0 => (dx, dy),
+                1 => (dy, -dx),
+                2 => (-dx, -dy),
+                _ => (-dy, dx)
+            };
+            let pixel = |dx : isize, dy : isize| {
+                let (dx, dy) = rotate(dx, dy);
+                (n.get(x, y, dx, dy), yuv_neighbourhood.get(x, y, dx, dy))
+            };
+            let block_index = |dx : isize, dy : isize| {
+                let (dx, dy) = rotate(dx, dy);
+                (dy > 0) as usize * 2 + (dx > 0) as usize
+            };
+
+            let [e, i, h, f, g, c, d, b] = [(0, 0), (1, 1), (0, 1), (1, 0), (-1, 1), (1, -1), (-1, 0), (0, -1)].map(|(dx, dy)| pixel(dx, dy));
+            let [f4, i4, h5, i5] = [(2, 0), (2, 1), (0, 2), (1, 2)].map(|(dx, dy)| pixel(dx, dy));
+            let (corner, left_of_corner, above_corner) = (block_index(1, 1), block_index(-1, 1), block_index(1, -1));
+
+            let df = |p : (Color32, [i32 ; 3]), q : (Color32, [i32 ; 3])| (0..3).map(|channel| (p.1[channel] - q.1[channel]).abs()).sum::<i32>();
+            let eq = |p, q| df(p, q) < 155;
+
+            if e.0 == h.0 || e.0 == f.0 {
+                continue;
+            }
+
+            let weight_e = df(e, c) + df(e, g) + df(i, h5) + df(i, f4) + (df(h, f) << 2);
+            let weight_i = df(h, d) + df(h, i5) + df(f, i4) + df(f, b) + (df(e, i) << 2);
+            if weight_e > weight_i {
+                continue;
+            }
+
+            let new_color = if df(e, f) <= df(e, h) { f.0 } else { h.0 };
+            if weight_e < weight_i && ((!eq(f, b) && !eq(h, d)) || (eq(e, i) && !eq(f, i4) && !eq(h, i5)) || eq(e, g) || eq(e, c)) {
+                let (ke, ki) = (df(f, g), df(h, c));
+                let left = ke << 1 <= ki && e.0 != g.0 && d.0 != g.0;
+                let up = ke >= ki << 1 && e.0 != c.0 && b.0 != c.0;
+
+                if left && up {
+                    block[corner] = blend_towards(block[corner], new_color, 7, 3);
+                    block[left_of_corner] = blend_towards(block[left_of_corner], new_color, 1, 2);
+                    block[above_corner] = block[left_of_corner];
+                } else if left {
+                    block[corner] = blend_towards(block[corner], new_color, 3, 2);
+                    block[left_of_corner] = blend_towards(block[left_of_corner], new_color, 1, 2);
+                } else if up {
+                    block[corner] = blend_towards(block[corner], new_color, 3, 2);
+                    block[above_corner] = blend_towards(block[above_corner], new_color, 1, 2);
+                } else {
+                    block[corner] = blend_half(block[corner], new_color);
+                }
+            } else {
+                block[corner] = blend_half(block[corner], new_color);
+            }
+        }
+
+        block
+    })
+}
+
+// The thresholds hqx uses to decide whether two colors are different
+fn yuv_differs(a : [i32 ; 3], b : [i32 ; 3]) -> bool {
+    (a[0] - b[0]).abs() > 48 || (a[1] - b[1]).abs() > 7 || (a[2] - b[2]).abs() > 6
+}
+
+/*
+Maxim Stepin's hq2x.  The original is a table of the 256 patterns of which of the 8 neighbours differ from the pixel,
+with the blend for each of the 4 output pixels.  This is the same table in the condensed form from FFmpeg's hqx filter,
+which works out the top left output pixel from the patterns that lead to each blend, and gets the other three by
+mirroring the neighbourhood.  The neighbourhood is numbered row by row:
+
+    0 1 2
+    3 4 5
+    6 7 8
+*/
+fn hq2x(pixels : &[Color32], width : usize, height : usize) -> Vec<Color32> {
+    let yuv_pixels : Vec<[i32 ; 3]> = pixels.iter().map(|c| yuv(*c)).collect();
+    let yuv_neighbourhood = Neighbourhood { pixels : &yuv_pixels, width, height };
+
+    upscale_blocks(pixels, width, height, 2, |n, x, y| {
+        let w : [(Color32, [i32 ; 3]) ; 9] = std::array::from_fn(|i| {
+            let (dx, dy) = ((i % 3) as isize - 1, (i / 3) as isize - 1);
+            (n.get(x, y, dx, dy), yuv_neighbourhood.get(x, y, dx, dy))
+        });
+
+        // Bits 0-7 are whether each of the neighbours differs from 4, skipping 4 itself
+        let pattern = [0, 1, 2, 3, 5, 6, 7, 8].iter().enumerate().fold(0u8, |pattern, (bit, i)| {
+            pattern | (((w[4].0 != w[*i].0 && yuv_differs(w[4].1, w[*i].1)) as u8) << bit)
+        });
+
+        [
+            hq2x_corner(&w, pattern, [0, 1, 2, 3, 4, 5, 6, 7, 8]),
+            hq2x_corner(&w, pattern, [2, 1, 0, 5, 4, 3, 8, 7, 6]),
+            hq2x_corner(&w, pattern, [6, 7, 8, 3, 4, 5, 0, 1, 2]),
+            hq2x_corner(&w, pattern, [8, 7, 6, 5, 4, 3, 2, 1, 0])
+        ]
+    })
+}
+
+// The top left output pixel of hq2x, with the neighbourhood rearranged by order so that any corner can be made the top
+// left one
+fn hq2x_corner(w : &[(Color32, [i32 ; 3]) ; 9], pattern : u8, order : [usize ; 9]) -> Color32 {
+    let bit = |i : usize| if i > 4 { i - 1 } else { i };
+    let pattern = (0..9).filter(|i| *i != 4).fold(0u8, |k, i| k | (((pattern >> bit(order[i])) & 1) << bit(i)));
+
+    // Whether the pattern matches any of the masks, as (the neighbours that matter, which of them differ)
+    let matches = |masks : &[(u8, u8)]| masks.iter().any(|(mask, differ)| pattern & mask == *differ);
+    let differs = |a : usize, b : usize| yuv_differs(w[order[a]].1, w[order[b]].1);
+    let [w0, w1, _, w3, w4, _, _, _, _] = order.map(|i| w[i].0);
+
+    if matches(&[(0xbf, 0x37), (0xdb, 0x13)]) && differs(1, 5) {
+        return interpolate(&[(w4, 3), (w3, 1)], 2);
+    }
+    if matches(&[(0xdb, 0x49), (0xef, 0x6d)]) && differs(7, 3) {
+        return interpolate(&[(w4, 3), (w1, 1)], 2);
+    }
+    if matches(&[(0x0b, 0x0b), (0xfe, 0x4a), (0xfe, 0x1a)]) && differs(3, 1) {
+        return w4;
+    }
+    if matches(&[(0x6f, 0x2a), (0x5b, 0x0a), (0xbf, 0x3a), (0xdf, 0x5a), (0x9f, 0x8a), (0xcf, 0x8a), (0xef, 0x4e), (0x3f, 0x0e),
+        (0xfb, 0x5a), (0xbb, 0x8a), (0x7f, 0x5a), (0xaf, 0x8a), (0xeb, 0x8a)]) && differs(3, 1) {
+        return interpolate(&[(w4, 3), (w0, 1)], 2);
+    }
+    if matches(&[(0x0b, 0x08)]) {
+        return interpolate(&[(w4, 2), (w0, 1), (w1, 1)], 2);
+    }
+    if matches(&[(0x0b, 0x02)]) {
+        return interpolate(&[(w4, 2), (w0, 1), (w3, 1)], 2);
+    }
+    if matches(&[(0x2f, 0x2f)]) {
+        return interpolate(&[(w4, 14), (w3, 1), (w1, 1)], 4);
+    }
+    if matches(&[(0xbf, 0x37), (0xdb, 0x13)]) {
+        return interpolate(&[(w4, 5), (w1, 2), (w3, 1)], 3);
+    }
+    if matches(&[(0xdb, 0x49), (0xef, 0x6d)]) {
+        return interpolate(&[(w4, 5), (w3, 2), (w1, 1)], 3);
+    }
+    if matches(&[(0x1b, 0x03), (0x4f, 0x43), (0x8b, 0x83), (0x6b, 0x43)]) {
+        return interpolate(&[(w4, 3), (w3, 1)], 2);
+    }
+    if matches(&[(0x4b, 0x09), (0x8b, 0x89), (0x1f, 0x19), (0x3b, 0x19)]) {
+        return interpolate(&[(w4, 3), (w1, 1)], 2);
+    }
+    if matches(&[(0x7e, 0x2a), (0xef, 0xab), (0xbf, 0x8f), (0x7e, 0x0e)]) {
+        return interpolate(&[(w4, 2), (w3, 3), (w1, 3)], 3);
+    }
+    if matches(&[(0xfb, 0x6a), (0x6f, 0x6e), (0x3f, 0x3e), (0xfb, 0xfa), (0xdf, 0xde), (0xdf, 0x1e)]) {
+        return interpolate(&[(w4, 3), (w0, 1)], 2);
+    }
+    if matches(&[(0x0a, 0x00), (0x4f, 0x4b), (0x9f, 0x1b), (0x2f, 0x0b), (0xbe, 0x0a), (0xee, 0x0a), (0x7e, 0x0a), (0xeb, 0x4b),
+        (0x3b, 0x1b)]) {
+        return interpolate(&[(w4, 2), (w3, 1), (w1, 1)], 2);
+    }
+    interpolate(&[(w4, 6), (w3, 1), (w1, 1)], 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A diagonal staircase of black on white, about the simplest thing that looks different after each upscaler
+    fn staircase() -> Vec<u8> {
+        let mut pixels = vec![0x0F ; 16];
+        for i in 0..4 {
+            for x in 0..=i {
+                pixels[i * 4 + x] = 0x00;
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn scale2x_smooths_diagonals() {
+        let image = Upscaler::Scale2x.upscale(&staircase(), 4, 4, &Palette::EGA);
+        assert_eq!((image.width, image.height), (8, 8));
+        let UpscaledPixels::Indexed(scaled) = &image.pixels else { panic!("Scale2x should keep palette indexes") };
+        // The black pixel at (1,1) has white above and to its right, so that corner gets rounded off
+        assert_eq!(&scaled[2 * 8..][..4], &[0x00, 0x00, 0x00, 0x0F]);
+        assert_eq!(&scaled[3 * 8..][..4], &[0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn scale3x_output_size_and_corners() {
+        let image = Upscaler::Scale3x.upscale(&staircase(), 4, 4, &Palette::EGA);
+        assert_eq!((image.width, image.height), (12, 12));
+        let UpscaledPixels::Indexed(scaled) = &image.pixels else { panic!("Scale3x should keep palette indexes") };
+        // Top right corner of the block for (1,1)
+        assert_eq!(scaled[3 * 12 + 5], 0x0F);
+        assert_eq!(scaled[3 * 12 + 3], 0x00);
+    }
+
+    #[test]
+    fn blending_upscalers_soften_edges_but_not_flat_areas() {
+        for upscaler in [Upscaler::Xbr, Upscaler::Hq2x] {
+            let image = upscaler.upscale(&staircase(), 4, 4, &Palette::EGA);
+            let colors = image.to_colors(&Palette::EGA);
+            assert_eq!(colors.len(), 64);
+            // Deep inside the black and white areas nothing changes
+            assert_eq!(colors[7 * 8], Color32::BLACK, "{}", upscaler.name());
+            assert_eq!(colors[7], Color32::WHITE, "{}", upscaler.name());
+            // But the staircase gets some in between colors
+            assert!(colors.iter().any(|c| *c != Color32::BLACK && *c != Color32::WHITE), "{}", upscaler.name());
+        }
+    }
+    #[test]
+    fn isolated_pixels_get_the_reference_blends() {
+        let mut pixels = vec![0x0F ; 9];
+        pixels[4] = 0x00;
+
+        // 2xBR blends each corner half way with its neighbours, hq2x takes 1/16 of each of them, as in the originals
+        for (upscaler, grey) in [(Upscaler::Xbr, 127), (Upscaler::Hq2x, 31)] {
+            let colors = upscaler.upscale(&pixels, 3, 3, &Palette::EGA).to_colors(&Palette::EGA);
+            for (x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)] {
+                assert_eq!(colors[y * 6 + x], Color32::from_gray(grey), "{} at ({},{})", upscaler.name(), x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use byteorder::*;
+use serde::{Serialize, Serializer};
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AgiResourceType {
+    Logic,
+    Picture,
+    View,
+    Sound,
+    Other
+}
+
+// The position in a directory entry is 20 bits, so nothing can start further into a VOL file than this
+const MAX_VOL_FILE_OFFSET : usize = 0xFFFFF;
+const RESOURCE_SIGNATURE : [u8 ; 2] = [0x12, 0x34];
+
+/// A resource read from one of a game's VOL files, serializing as its metadata with the raw data replaced by its size
+#[derive(Debug, Clone, Serialize)]
+pub struct Resource {
+    resource_type : AgiResourceType,
+    resource_index : usize,
+    vol_file: u8,
+    vol_file_offset: usize,
+    #[serde(rename = "size", serialize_with = "serialize_len")]
+    raw_data: Vec<u8>
+}
+
+fn serialize_len<S : Serializer>(data : &[u8], serializer : S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(data.len() as u64)
+}
+
+/*
+From: http://www.agidev.com/articles/agispec/agispecs-5.html
+
+Each directory file is of the same format. They contain a finite number of three byte entries, no more than 256. The size will vary depending on the number of files of the type that the directory file is pointing to. Dividing the filesize by three gives the maximum file number of that type of data file. Each entry is of the following format:
+
+    Byte 1           Byte 2           Byte 3
+7 6 5 4 3 2 1 0  7 6 5 4 3 2 1 0  7 6 5 4 3 2 1 0
+V V V V P P P P  P P P P P P P P  P P P P P P P P
+where V = VOL number and P = position (offset into VOL file).
+
+The entry number itself gives the number of the data file that it is pointing to. For example, if the following three byte entry is entry number 45 in the SOUND directory file,
+
+12 3D FE
+then sound.45 is located at position 0x23DFE in the vol.1 file. The first entry number is entry 0.
+
+If the three bytes contain the value 0xFFFFFF, then the resource does not exist.
+*/
+impl Resource {
+    /// The resource's data, without the five byte VOL header
+    pub fn get_raw_data(&self) -> &Vec<u8> {
+        &self.raw_data
+    }
+
+    pub fn get_resource_type(&self) -> &AgiResourceType {
+        &self.resource_type
+    }
+
+    /// The resource's number, its entry in the directory file
+    pub fn get_resource_index(&self) -> usize {
+        self.resource_index
+    }
+
+    pub fn get_vol_file(&self) -> u8 {
+        self.vol_file
+    }
+
+    pub fn get_vol_file_offset(&self) -> usize {
+        self.vol_file_offset
+    }
+
+    /// Reads entry resource_index of a directory file, returning None if the entry marks the resource as missing
+    pub fn new(resource_type : AgiResourceType, directory_file_stream : &[u8], resource_index : usize, volume_files : &[Vec<u8>] ) -> Result<Option<Self>, AgiError> {
+        let stream_offset = resource_index * 3;
+
+        if stream_offset + 3 > directory_file_stream.len() {
+            Err(AgiError::Parse(format!("Stream was too short, asked for index {}, but only have {} bytes", resource_index, directory_file_stream.len())))
+        } else {
+            let vol_file : u8 = directory_file_stream[stream_offset] >> 4;
+            let vol_file_offset : usize = 
+                (((directory_file_stream[stream_offset] as usize) & 0xFusize) << 16) |
+                ((directory_file_stream[stream_offset+1] as usize) << 8) |
+                (directory_file_stream[stream_offset+2] as usize);
+
+            // Read the data from the volume file
+            if vol_file == 0xF {
+                Ok(None)
+            } else if vol_file as usize >= volume_files.len()  && vol_file != 0xF {
+               Err(AgiError::Parse(format!("Attempted to access invalid volume file index {}", vol_file)))
+            } else {
+                // Read the data from the volume file
+                let my_vol_file_data = &volume_files[vol_file as usize];
+
+                // The header is the signature, the volume number and the length of the data
+                let header = my_vol_file_data.get(vol_file_offset..vol_file_offset + 5)
+                    .ok_or_else(|| AgiError::Parse(format!("Resource header at {:#x} is past the end of volume file {}", vol_file_offset, vol_file)))?;
+
+                let signature : u16 = LittleEndian::read_u16(&header[0..=1]);
+                let resource_len : usize = LittleEndian::read_u16(&header[3..=4]) as usize;
+
+                if signature != 0x3412 {
+                    return Err(AgiError::Parse(format!("Expected signature 0x3412, got {:#04x}", signature)))
+                }
+
+                let raw_data = my_vol_file_data.get(vol_file_offset + 5..vol_file_offset + 5 + resource_len)
+                    .ok_or_else(|| AgiError::Parse(format!("Resource at {:#x} runs {} bytes past the end of volume file {}",
+                        vol_file_offset, vol_file_offset + 5 + resource_len - my_vol_file_data.len(), vol_file)))?;
+
+                Ok(Some(Self { resource_type, resource_index, vol_file, vol_file_offset, raw_data: raw_data.to_vec() }))
+            }
+        }
+    }
+
+    /// Replaces the resource's data.  If the new data fits where the old data was, it's written over it, otherwise it's
+    /// appended to the end of its VOL file and its directory entry pointed there, leaving the old data where it was as
+    /// the original tools did.  Either way, other resources never move.
+    pub fn rewrite(&mut self, raw_data : Vec<u8>, directory_file : &mut [u8], volume_file : &mut Vec<u8>) -> Result<(), AgiError> {
+        let entry = directory_file.get_mut(self.resource_index * 3..self.resource_index * 3 + 3)
+            .ok_or_else(|| AgiError::Export(format!("The directory file has no entry {}", self.resource_index)))?;
+
+        let resource_len = u16::try_from(raw_data.len())
+            .map_err(|_| AgiError::Export(format!("{} bytes is too big for a resource", raw_data.len())))?;
+
+        let mut record = RESOURCE_SIGNATURE.to_vec();
+        record.push(self.vol_file);
+        record.extend(resource_len.to_le_bytes());
+        record.extend(&raw_data);
+
+        // Reusing the old slot keeps repeated saves of the same picture from growing the file
+        let vol_file_offset = if raw_data.len() <= self.raw_data.len() {
+            volume_file.get_mut(self.vol_file_offset..self.vol_file_offset + record.len())
+                .ok_or_else(|| AgiError::Export(format!("Volume file {} has no resource at {:#x}", self.vol_file, self.vol_file_offset)))?
+                .copy_from_slice(&record);
+            self.vol_file_offset
+        } else {
+            let vol_file_offset = volume_file.len();
+            if vol_file_offset > MAX_VOL_FILE_OFFSET {
+                return Err(AgiError::Export(format!("Volume file {} is full, it's already {} bytes", self.vol_file, vol_file_offset)));
+            }
+            volume_file.extend(record);
+            vol_file_offset
+        };
+
+        entry.copy_from_slice(&[
+            (self.vol_file << 4) | (vol_file_offset >> 16 & 0x0F) as u8,
+            (vol_file_offset >> 8 & 0xFF) as u8,
+            (vol_file_offset & 0xFF) as u8
+        ]);
+
+        self.vol_file_offset = vol_file_offset;
+        self.raw_data = raw_data;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write, path::Path};
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn generate_sample_files() {
+        // This is a little piece of code to package up a few PIC resources for samples
+        // It is used to generate the sample test set
+        let paths_and_pics = [
+            (Path::new("C:\\GOG Games\\Kings Quest\\"), vec![0,1,7,11,14,15,16,21,27,41,43,44,52,71,78]),
+            (Path::new("C:\\GOG Games\\Kings Quest 2\\"), vec![0,1,2,3,6,8,9,10,19,20,43,58,59,63,67,69,96]),
+            (Path::new("C:\\GOG Games\\Kings Quest 3\\"), vec![0,1,2,3,4,5,12,13,14,23,24,27,46,47,53,54,67,80,81]),
+        ];
+
+        let mut offset = 0usize;
+        let mut volume_data : Vec<u8> = vec![];
+        let mut picdir_data : Vec<u8> = vec![];
+
+        let sig = [0x12u8, 0x34u8];
+
+        for (path, pics) in paths_and_pics.iter() {
+            if let Ok(game) = Game::new_from_dir(path) {
+                for (pic_index, resource) in game.all_resources
+                    .iter()
+                    .filter(|r| r.resource_type == AgiResourceType::Picture)
+                    .enumerate() {
+
+                    if pics.contains(&pic_index) {
+                        let index_data = [
+                            (offset >> 16 & 0x0Fusize) as u8,
+                            (offset >> 8 & 0xFFusize) as u8,
+                            (offset & 0xFFusize) as u8
+                        ];
+                        picdir_data.extend(&index_data[..]);
+
+                        let raw_data = resource.get_raw_data();
+
+                        // Write signature
+                        volume_data.extend(sig);
+
+                        // Write volume index
+                        volume_data.push(0);
+
+                        // Write resource length
+                        let mut len = [0u8; 2];
+                        LittleEndian::write_u16(&mut len, raw_data.len() as u16);
+                        volume_data.extend(len);
+
+                        // Write data
+                        volume_data.extend(raw_data);
+
+                        offset += raw_data.len() + 5;
+                    }
+                }
+            }
+        }
+
+        // Write the samples to our files
+        let mut volume_file = File::create("VOL.0").unwrap();
+        volume_file.write_all(&volume_data).unwrap();
+
+        let mut picdir_file = File::create("PICDIR").unwrap();
+        picdir_file.write_all(&picdir_data).unwrap();
+    }
+
+    #[test]
+    fn truncated_resources_are_errors() {
+        let volumes = [vec![0x12, 0x34, 0x00, 0x04, 0x00, 0xF0, 0x01, 0xFF]];
+
+        assert!(Resource::new(AgiResourceType::Picture, &[0x00, 0x00], 0, &volumes).is_err());
+        assert!(Resource::new(AgiResourceType::Picture, &[0x00, 0x00, 0x00], 0, &[volumes[0][..4].to_vec()]).is_err());
+        assert!(Resource::new(AgiResourceType::Picture, &[0x00, 0x00, 0x00], 0, &volumes).is_err());
+        assert!(Resource::new(AgiResourceType::Picture, &[0x10, 0x00, 0x00], 0, &volumes).is_err());
+
+        let mut complete = volumes[0].clone();
+        complete.push(0xFF);
+        assert_eq!(Resource::new(AgiResourceType::Picture, &[0x00, 0x00, 0x00], 0, &[complete]).unwrap().unwrap().get_raw_data(), &vec![0xF0, 0x01, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn rewrites_that_fit_reuse_the_old_slot() {
+        let mut volume = vec![0x12, 0x34, 0x00, 0x04, 0x00, 0xF0, 0x01, 0xF1, 0xFF];
+        let mut directory = vec![0x00, 0x00, 0x00];
+        let mut resource = Resource::new(AgiResourceType::Picture, &directory, 0, &[volume.clone()]).unwrap().unwrap();
+
+        for raw_data in [vec![0xF0, 0x02, 0xF1, 0xFF], vec![0xF1, 0xFF]] {
+            resource.rewrite(raw_data.clone(), &mut directory, &mut volume).unwrap();
+            assert_eq!(volume.len(), 9);
+            assert_eq!(directory, [0x00, 0x00, 0x00]);
+
+            let reread = Resource::new(AgiResourceType::Picture, &directory, 0, &[volume.clone()]).unwrap().unwrap();
+            assert_eq!(reread.get_raw_data(), &raw_data);
+        }
+    }
+
+    #[test]
+    fn rewritten_resources_read_back() {
+        let mut volume = vec![0x12, 0x34, 0x00, 0x03, 0x00, 0xF0, 0x01, 0xFF];
+        let mut directory = vec![0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00];
+        let mut resource = Resource::new(AgiResourceType::Picture, &directory, 1, &[volume.clone()]).unwrap().unwrap();
+
+        resource.rewrite(vec![0xF0, 0x02, 0xF1, 0xFF], &mut directory, &mut volume).unwrap();
+        assert_eq!(directory, [0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x08]);
+        assert_eq!(resource.get_vol_file_offset(), 8);
+
+        let reread = Resource::new(AgiResourceType::Picture, &directory, 1, &[volume.clone()]).unwrap().unwrap();
+        assert_eq!(reread.get_raw_data(), &vec![0xF0, 0x02, 0xF1, 0xFF]);
+        assert_eq!(reread.get_vol_file_offset(), 8);
+
+        // The old copy is still there, but nothing points at it
+        assert_eq!(&volume[..8], &[0x12, 0x34, 0x00, 0x03, 0x00, 0xF0, 0x01, 0xFF]);
+
+        let mut full_volume = vec![0 ; MAX_VOL_FILE_OFFSET + 1];
+        assert!(resource.rewrite(vec![0xFF ; 8], &mut directory, &mut full_volume).is_err());
+        assert!(resource.rewrite(vec![0xFF ; 0x10000], &mut directory, &mut volume).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn generate_fuzz_corpus() {
+        // Seeds the fuzzing corpus from the sample pics, see the fuzz_targets for the input formats
+        let sample_pics_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics");
+        let corpus_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz").join("corpus");
+
+        let pic_data = std::fs::read(sample_pics_dir.join("PICDIR")).unwrap();
+        let volume_data = std::fs::read(sample_pics_dir.join("VOL.0")).unwrap();
+
+        let mut game_data = vec![(pic_data.len() / 3) as u8];
+        game_data.extend(&pic_data);
+        game_data.extend(&volume_data);
+        std::fs::create_dir_all(corpus_dir.join("game_data")).unwrap();
+        std::fs::write(corpus_dir.join("game_data").join("sample_pics"), game_data).unwrap();
+
+        let game = Game::new_from_data("sample_pics", &pic_data, &[volume_data]);
+        for target in ["pic_parse", "pic_render"] {
+            std::fs::create_dir_all(corpus_dir.join(target)).unwrap();
+            for (pic_index, resource) in game.all_resources.iter().enumerate() {
+                let mut data = resource.get_raw_data().clone();
+                if target == "pic_render" {
+                    // The render options byte
+                    data.insert(0, (pic_index % 4) as u8);
+                }
+                std::fs::write(corpus_dir.join(target).join(format!("sample_pic_{:03}", pic_index)), data).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/*
+Renders every sample picture and priority screen and compares them against the reference PNGs in sample_pics/golden.
+
+The reference PNGs are generated by this library's own renderer with generate_golden_images below, so these tests
+only catch regressions from whatever it drew when they were last generated, not whether that was right.  Their
+correctness is checked separately, against the independent renderer in reference_renders.rs.
+
+When a render differs, a diff image and report are written to target/tmp/golden-diffs.  The image shows the reference,
+the new render and the differences side by side, with each differing pixel coloured by the instruction that drew it in
+the new render, and the report lists the differing pixels by instruction.
+
+After an intended rendering change, regenerate the references with:
+  cargo test -p agi-types --test golden_images -- --ignored generate_golden_images
+*/
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use agi_types::egui::Color32;
+use agi_types::pic_export::*;
+use agi_types::*;
+
+const DIFF_SCALE : usize = 2;
+
+// x, y, expected colour, rendered colour
+type PixelDifference = (usize, usize, Color32, Color32);
+
+fn sample_pics_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")
+}
+
+fn golden_path(prefix : &str, pic : usize) -> PathBuf {
+    sample_pics_dir().join("golden").join(format!("{}_{:03}.png", prefix, pic))
+}
+
+fn diff_dir(name : &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+fn render(pic : &PicResource) -> [(&'static str, PixelBuffer); 2] {
+    let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+    let mut pri_buffer = PixelBuffer::new(PRI_BUFFER_BASE_COLOR);
+    render_to_buffers(pic.get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut None).unwrap();
+    [("pic", pic_buffer), ("pri", pri_buffer)]
+}
+
+// Reads a reference as RGB, whatever format it was saved in
+fn read_png(path : &Path) -> Result<Vec<Color32>, String> {
+    let mut decoder = png::Decoder::new(File::open(path).map_err(|e| e.to_string())?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+
+    let mut data = vec![0u8; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+
+    if (frame.width as usize, frame.height as usize) != (VIEWPORT_WIDTH, VIEWPORT_HEIGHT) {
+        return Err(format!("Expected a {}x{} image, got {}x{}", VIEWPORT_WIDTH, VIEWPORT_HEIGHT, frame.width, frame.height));
+    }
+
+    let data = &data[..frame.buffer_size()];
+    match frame.color_type {
+        png::ColorType::Rgb => Ok(data.chunks_exact(3).map(|p| Color32::from_rgb(p[0], p[1], p[2])).collect()),
+        png::ColorType::Rgba => Ok(data.chunks_exact(4).map(|p| Color32::from_rgb(p[0], p[1], p[2])).collect()),
+        png::ColorType::Grayscale => Ok(data.iter().map(|g| Color32::from_gray(*g)).collect()),
+        png::ColorType::GrayscaleAlpha => Ok(data.chunks_exact(2).map(|p| Color32::from_gray(p[0])).collect()),
+        color_type => Err(format!("Unexpected colour type {:?}", color_type))
+    }
+}
+
+// A distinct, bright colour for each instruction so neighbouring instructions' pixels can be told apart
+fn instruction_color(instruction : Option<InstructionIndex>) -> Color32 {
+    match instruction {
+        Some(index) => {
+            let hue = (index.get_base_index() as f32 * 0.618034).fract();
+            let channel = |offset : f32| {
+                let t = ((hue + offset).fract() * 6.0 - 3.0).abs() - 1.0;
+                (t.clamp(0.0, 1.0) * 255.0) as u8
+            };
+            Color32::from_rgb(channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0))
+        },
+        None => Color32::WHITE
+    }
+}
+
+fn dim(color : Color32) -> Color32 {
+    let grey = ((color.r() as u32 + color.g() as u32 + color.b() as u32) / 6) as u8;
+    Color32::from_gray(grey)
+}
+
+fn hex(color : Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+fn write_diff_image(path : &Path, expected : &[Color32], buffer : &PixelBuffer) {
+    let panel_width = VIEWPORT_WIDTH * DIFF_SCALE;
+    let (width, height) = (panel_width * 3, VIEWPORT_HEIGHT * DIFF_SCALE);
+    let mut rgb = vec![0u8; width * height * 3];
+
+    for y in 0..height {
+        for x in 0..width {
+            let (panel, px, py) = (x / panel_width, (x % panel_width) / DIFF_SCALE, y / DIFF_SCALE);
+            let expected_color = expected[py * VIEWPORT_WIDTH + px];
+            let actual_color = buffer.get_pixel(px, py).unwrap();
+
+            let color = match panel {
+                0 => expected_color,
+                1 => actual_color,
+                _ if expected_color == actual_color => dim(expected_color),
+                _ => instruction_color(buffer.get_pixel_instruction(px, py).unwrap())
+            };
+
+            rgb[(y * width + x) * 3..][..3].copy_from_slice(&[color.r(), color.g(), color.b()]);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path).unwrap()), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(&rgb).unwrap();
+}
+
+// Returns a description of the differences if the buffer doesn't match its reference, writing the diff image and report
+// to out_dir
+fn compare(prefix : &str, pic_index : usize, pic : &PicResource, buffer : &PixelBuffer, out_dir : &Path) -> Option<String> {
+    let name = format!("{}_{:03}", prefix, pic_index);
+    let expected = match read_png(&golden_path(prefix, pic_index)) {
+        Ok(expected) => expected,
+        Err(err) => return Some(format!("{}: can't read the reference image: {}", name, err))
+    };
+
+    // Differing pixels grouped by the instruction that drew them in the new render
+    let mut differences : BTreeMap<Option<usize>, Vec<PixelDifference>> = BTreeMap::new();
+    for y in 0..VIEWPORT_HEIGHT {
+        for x in 0..VIEWPORT_WIDTH {
+            let (expected_color, actual_color) = (expected[y * VIEWPORT_WIDTH + x], buffer.get_pixel(x, y).unwrap());
+            if expected_color != actual_color {
+                let instruction = buffer.get_pixel_instruction(x, y).unwrap().map(|i| i.get_base_index());
+                differences.entry(instruction).or_default().push((x, y, expected_color, actual_color));
+            }
+        }
+    }
+
+    if differences.is_empty() {
+        return None;
+    }
+
+    fs::create_dir_all(out_dir).unwrap();
+    write_diff_image(&out_dir.join(format!("{}_diff.png", name)), &expected, buffer);
+
+    let mut report = String::new();
+    for (instruction, pixels) in &differences {
+        let description = match instruction {
+            Some(index) => format!("Instruction {} ({})", index, pic.get_instructions()[*index]),
+            None => String::from("Not drawn by any instruction")
+        };
+        report.push_str(&format!("{}: {} pixels\n", description, pixels.len()));
+        for (x, y, expected_color, actual_color) in pixels {
+            report.push_str(&format!("  ({},{}) expected {} got {}\n", x, y, hex(*expected_color), hex(*actual_color)));
+        }
+    }
+    fs::write(out_dir.join(format!("{}_diff.txt", name)), &report).unwrap();
+
+    let pixel_count : usize = differences.values().map(|p| p.len()).sum();
+    let instructions : Vec<String> = differences.keys().map(|i| i.map_or(String::from("none"), |i| i.to_string())).collect();
+    Some(format!("{}: {} pixels differ, drawn by instructions {}", name, pixel_count, instructions.join(", ")))
+}
+
+#[test]
+fn sample_pics_match_golden_images() {
+    let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+    let out_dir = diff_dir("golden-diffs");
+    let _ = fs::remove_dir_all(&out_dir);
+
+    let mut failures = vec![];
+    for (pic_index, pic) in game.pic_resources.iter().enumerate() {
+        for (prefix, buffer) in render(pic) {
+            failures.extend(compare(prefix, pic_index, pic, &buffer, &out_dir));
+        }
+    }
+
+    assert!(failures.is_empty(), "{} renders differ from their references, see {} for diffs:\n{}",
+        failures.len(), out_dir.display(), failures.join("\n"));
+}
+
+#[test]
+fn differences_are_reported() {
+    // Compare a picture against another's references, which should fail and leave a diff behind
+    let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+    let [(prefix, buffer), _] = render(&game.pic_resources[1]);
+    let out_dir = diff_dir("golden-diffs-self-test");
+
+    let failure = compare(prefix, 0, &game.pic_resources[1], &buffer, &out_dir).unwrap();
+    assert!(failure.starts_with("pic_000: ") && failure.contains("drawn by instructions"), "{}", failure);
+
+    let report = fs::read_to_string(out_dir.join("pic_000_diff.txt")).unwrap();
+    assert!(report.starts_with("Instruction ") || report.starts_with("Not drawn"));
+    assert_eq!(read_png(&out_dir.join("pic_000_diff.png")).err(), Some(format!("Expected a {}x{} image, got {}x{}",
+        VIEWPORT_WIDTH, VIEWPORT_HEIGHT, VIEWPORT_WIDTH * DIFF_SCALE * 3, VIEWPORT_HEIGHT * DIFF_SCALE)));
+}
+
+#[test]
+#[ignore]
+fn generate_golden_images() {
+    // Regenerates the reference images, only run this when a rendering change is intended
+    let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+    fs::create_dir_all(sample_pics_dir().join("golden")).unwrap();
+
+    for (pic_index, pic) in game.pic_resources.iter().enumerate() {
+        for (prefix, buffer) in render(pic) {
+            fs::write(golden_path(prefix, pic_index), export_buffer(&buffer, &ImageExportOptions::default()).unwrap()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,330 @@
+/*
+An independent renderer for the sample pictures, used to generate sample_pics/reference_renders.txt, which the
+library's own renderer is then checked against.
+
+It shares no code with agi_types: it reads PICDIR and VOL.0 itself, decodes the picture bytes as they're described in
+the AGI Specifications, draws lines with the Bresenham style stepping in ScummVM's AGI engine (PictureMgr::draw_Line)
+and fills with the queue based flood fill from the AGI Specifications, using the fill rules from ScummVM's
+PictureMgr::draw_FillCheck.  It's written for clarity rather than speed, one pixel at a time.
+
+It only covers what the sample pictures use, and stops on the pen instructions (0xF9 and 0xFA) rather than guess at
+them.
+
+To regenerate the references, e.g. after adding sample pictures:
+  cargo test -p agi-types --test reference_renders -- --ignored generate_reference_renders
+
+The golden images in sample_pics/golden are generated by the library itself, so they're also checked against this
+renderer here, which keeps them from drifting away from it between regenerations.  Cases the sample pictures don't
+cover are rendered by both this renderer and the library and compared directly.
+*/
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const WIDTH : usize = 160;
+const HEIGHT : usize = 168;
+const WHITE : u8 = 15;
+const RED : u8 = 4;
+
+// The EGA colours the golden images are saved with, by colour index
+const EGA : [[u8 ; 3] ; 16] = [
+    [0x00, 0x00, 0x00], [0x00, 0x00, 0xAA], [0x00, 0xAA, 0x00], [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00], [0xAA, 0x00, 0xAA], [0xAA, 0x55, 0x00], [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55], [0x55, 0x55, 0xFF], [0x55, 0xFF, 0x55], [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55], [0xFF, 0x55, 0xFF], [0xFF, 0xFF, 0x55], [0xFF, 0xFF, 0xFF]
+];
+
+const REFERENCE_RENDERS_FILE : &str = "reference_renders.txt";
+const REFERENCE_RENDERS_HEADER : &str = "\
+# Reference renders of the sample pictures: resource number, picture screen hash, priority screen hash.
+# The hashes are 64 bit FNV-1a over the colour index of each pixel, row by row.
+# Generated by the independent renderer in agi-types/tests/reference_renders.rs, which follows ScummVM's AGI line and
+# fill code and the AGI Specifications and shares no code with agi_types.  These are not captures from Sierra's
+# interpreter or from ScummVM itself.
+";
+
+fn sample_pics_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("sample_pics")
+}
+
+struct Screens {
+    picture : Vec<u8>,
+    priority : Vec<u8>,
+    // None when drawing to that screen is switched off
+    pic_color : Option<u8>,
+    pri_color : Option<u8>
+}
+
+impl Screens {
+    fn new() -> Self {
+        Screens { picture : vec![WHITE ; WIDTH * HEIGHT], priority : vec![RED ; WIDTH * HEIGHT], pic_color : None, pri_color : None }
+    }
+
+    fn put_pixel(&mut self, x : i32, y : i32) {
+        let index = y as usize * WIDTH + x as usize;
+        if let Some(color) = self.pic_color {
+            self.picture[index] = color;
+        }
+        if let Some(color) = self.pri_color {
+            self.priority[index] = color;
+        }
+    }
+
+    // ScummVM's draw_Line, straight lines are drawn directly and the rest are stepped along the longer axis, with an
+    // error term for the shorter one that starts half way so it's rounded rather than truncated
+    fn draw_line(&mut self, (x1, y1) : (i32, i32), (x2, y2) : (i32, i32)) {
+        if x1 == x2 {
+            for y in y1.min(y2)..=y1.max(y2) {
+                self.put_pixel(x1, y);
+            }
+            return;
+        }
+        if y1 == y2 {
+            for x in x1.min(x2)..=x1.max(x2) {
+                self.put_pixel(x, y1);
+            }
+            return;
+        }
+
+        let (step_x, delta_x) = ((x2 - x1).signum(), (x2 - x1).abs());
+        let (step_y, delta_y) = ((y2 - y1).signum(), (y2 - y1).abs());
+        let (steps, detdelta, mut error_x, mut error_y) = if delta_y > delta_x {
+            (delta_y, delta_y, delta_y / 2, 0)
+        } else {
+            (delta_x, delta_x, 0, delta_x / 2)
+        };
+
+        let (mut x, mut y) = (x1, y1);
+        self.put_pixel(x, y);
+        for _ in 0..steps {
+            error_y += delta_y;
+            if error_y >= detdelta {
+                error_y -= detdelta;
+                y += step_y;
+            }
+            error_x += delta_x;
+            if error_x >= detdelta {
+                error_x -= detdelta;
+                x += step_x;
+            }
+            self.put_pixel(x, y);
+        }
+    }
+
+    // ScummVM's draw_FillCheck
+    fn ok_to_fill(&self, x : i32, y : i32) -> bool {
+        let index = y as usize * WIDTH + x as usize;
+        match (self.pic_color, self.pri_color) {
+            (Some(pic_color), None) if pic_color != WHITE => self.picture[index] == WHITE,
+            (None, Some(pri_color)) if pri_color != RED => self.priority[index] == RED,
+            (Some(pic_color), Some(_)) => pic_color != WHITE && self.picture[index] == WHITE,
+            _ => false
+        }
+    }
+
+    // The queue based flood fill from the AGI Specifications
+    fn fill(&mut self, x : i32, y : i32) {
+        let mut queue = VecDeque::from([(x, y)]);
+        while let Some((x, y)) = queue.pop_front() {
+            if !self.ok_to_fill(x, y) {
+                continue;
+            }
+            self.put_pixel(x, y);
+            if y != 0 && self.ok_to_fill(x, y - 1) {
+                queue.push_back((x, y - 1));
+            }
+            if x != 0 && self.ok_to_fill(x - 1, y) {
+                queue.push_back((x - 1, y));
+            }
+            if x != WIDTH as i32 - 1 && self.ok_to_fill(x + 1, y) {
+                queue.push_back((x + 1, y));
+            }
+            if y != HEIGHT as i32 - 1 && self.ok_to_fill(x, y + 1) {
+                queue.push_back((x, y + 1));
+            }
+        }
+    }
+}
+
+// Reads through the picture bytes, every argument is below 0xF0 and anything from 0xF0 up starts the next command
+struct PicReader<'a> {
+    data : &'a [u8],
+    position : usize
+}
+
+impl PicReader<'_> {
+    fn next_arg(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.position)?;
+        if byte >= 0xF0 {
+            return None;
+        }
+        self.position += 1;
+        Some(byte)
+    }
+
+    // Coordinates are clipped to the screen, as the interpreter does
+    fn next_point(&mut self) -> Option<(i32, i32)> {
+        let x = self.next_arg()?;
+        let y = self.next_arg()?;
+        Some((x.min(WIDTH as u8 - 1) as i32, y.min(HEIGHT as u8 - 1) as i32))
+    }
+}
+
+fn render(data : &[u8]) -> Screens {
+    let mut screens = Screens::new();
+    let mut reader = PicReader { data, position : 0 };
+
+    while let Some(&command) = data.get(reader.position) {
+        reader.position += 1;
+        match command {
+            // Only the low 4 bits of a colour are used
+            0xF0 => screens.pic_color = reader.next_arg().map(|color| color & 0x0F),
+            0xF1 => screens.pic_color = None,
+            0xF2 => screens.pri_color = reader.next_arg().map(|color| color & 0x0F),
+            0xF3 => screens.pri_color = None,
+            // Corners alternate between changing y and x, starting with the one in their name
+            0xF4 | 0xF5 => {
+                let Some(mut point) = reader.next_point() else { continue };
+                screens.put_pixel(point.0, point.1);
+                let mut change_y = command == 0xF4;
+                while let Some(arg) = reader.next_arg() {
+                    let next = if change_y { (point.0, arg.min(HEIGHT as u8 - 1) as i32) } else { (arg.min(WIDTH as u8 - 1) as i32, point.1) };
+                    screens.draw_line(point, next);
+                    point = next;
+                    change_y = !change_y;
+                }
+            },
+            0xF6 => {
+                let Some(mut point) = reader.next_point() else { continue };
+                screens.put_pixel(point.0, point.1);
+                while let Some(next) = reader.next_point() {
+                    screens.draw_line(point, next);
+                    point = next;
+                }
+            },
+            // Each step is a byte, bit 7 and bits 4-6 are the sign and size of the x step, bit 3 and bits 0-2 the y step
+            0xF7 => {
+                let Some(mut point) = reader.next_point() else { continue };
+                screens.put_pixel(point.0, point.1);
+                while let Some(step) = reader.next_arg() {
+                    let dx = ((step >> 4) & 0x07) as i32 * if step & 0x80 != 0 { -1 } else { 1 };
+                    let dy = (step & 0x07) as i32 * if step & 0x08 != 0 { -1 } else { 1 };
+                    let next = ((point.0 + dx).clamp(0, WIDTH as i32 - 1), (point.1 + dy).clamp(0, HEIGHT as i32 - 1));
+                    screens.draw_line(point, next);
+                    point = next;
+                }
+            },
+            0xF8 => {
+                while let Some((x, y)) = reader.next_point() {
+                    screens.fill(x, y);
+                }
+            },
+            0xFF => break,
+            _ => panic!("Command {:02X} isn't covered by the reference renderer", command)
+        }
+    }
+
+    screens
+}
+
+fn screen_hash(screen : &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for color in screen {
+        hash ^= *color as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Every picture in the sample game, straight from its PICDIR entries and VOL.0 resource headers
+fn sample_pictures() -> Vec<(usize, Vec<u8>)> {
+    let pic_dir = fs::read(sample_pics_dir().join("PICDIR")).unwrap();
+    let volume = fs::read(sample_pics_dir().join("VOL.0")).unwrap();
+
+    pic_dir.chunks_exact(3).enumerate().filter(|(_, entry)| entry[0] >> 4 != 0xF).map(|(resource, entry)| {
+        assert_eq!(entry[0] >> 4, 0, "The sample pictures are all in VOL.0");
+        let offset = ((entry[0] as usize & 0x0F) << 16) | ((entry[1] as usize) << 8) | entry[2] as usize;
+        assert_eq!(&volume[offset..offset + 2], &[0x12, 0x34], "Missing resource header for picture {}", resource);
+        let length = volume[offset + 3] as usize | (volume[offset + 4] as usize) << 8;
+        (resource, volume[offset + 5..offset + 5 + length].to_vec())
+    })
+    .collect()
+}
+
+// A golden image as colour indexes, going by its RGB values so it doesn't matter how the PNG stores them
+fn read_golden_image(name : &str) -> Vec<u8> {
+    let path = sample_pics_dir().join("golden").join(name);
+    let mut decoder = png::Decoder::new(File::open(&path).unwrap());
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16 | png::Transformations::ALPHA);
+    let mut reader = decoder.read_info().unwrap();
+
+    let mut data = vec![0u8 ; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut data).unwrap();
+    assert_eq!((frame.width as usize, frame.height as usize), (WIDTH, HEIGHT), "{} is the wrong size", name);
+
+    data[..frame.buffer_size()].chunks_exact(4).map(|pixel| {
+        EGA.iter().position(|color| color[..] == pixel[..3]).unwrap_or_else(|| panic!("{} has a colour outside the EGA palette: {:?}", name, pixel)) as u8
+    })
+    .collect()
+}
+
+fn reference_renders() -> String {
+    let mut renders = String::from(REFERENCE_RENDERS_HEADER);
+    for (resource, data) in sample_pictures() {
+        let screens = render(&data);
+        renders.push_str(&format!("{} {:016x} {:016x}\n", resource, screen_hash(&screens.picture), screen_hash(&screens.priority)));
+    }
+    renders
+}
+
+#[test]
+fn reference_renders_come_from_this_renderer() {
+    // Guards against the file being regenerated from anything else
+    let reference = fs::read_to_string(sample_pics_dir().join(REFERENCE_RENDERS_FILE)).unwrap();
+    assert_eq!(reference, reference_renders());
+}
+
+#[test]
+fn golden_images_match_this_renderer() {
+    // The golden images are numbered by the picture's place in the game rather than its resource number
+    for (pic_index, (resource, data)) in sample_pictures().into_iter().enumerate() {
+        let screens = render(&data);
+        for (prefix, screen) in [("pic", &screens.picture), ("pri", &screens.priority)] {
+            let name = format!("{}_{:03}.png", prefix, pic_index);
+            let golden = read_golden_image(&name);
+            let differing = screen.iter().zip(&golden).filter(|(expected, actual)| expected != actual).count();
+            assert_eq!(differing, 0, "{} (picture {}) differs from this renderer in {} pixels", name, resource, differing);
+        }
+    }
+}
+
+#[test]
+fn relative_lines_off_the_edges_match_the_library() {
+    // Each runs off an edge or corner and comes back, the last starting off the screen
+    let pictures : [&[u8] ; 4] = [
+        &[0xF0, 0x01, 0xF2, 0x02, 0xF7, 2, 165, 0xC3, 0xC3, 0x4B, 0x4B, 0x4B, 0xFF],
+        &[0xF0, 0x01, 0xF7, 157, 3, 0x7F, 0x7F, 0xF7, 0xF7, 0x70, 0xFF],
+        &[0xF0, 0x04, 0xF2, 0x05, 0xF7, 80, 1, 0x0F, 0x0F, 0x27, 0x27, 0xA7, 0xFF],
+        &[0xF0, 0x06, 0xF7, 220, 200, 0xCF, 0xCF, 0x47, 0xFF]
+    ];
+
+    for (i, data) in pictures.iter().enumerate() {
+        let screens = render(data);
+
+        let pic = agi_types::PicResource::new(data).unwrap();
+        let mut pic_buffer = agi_types::PixelBuffer::new(agi_types::PIC_BUFFER_BASE_COLOR);
+        let mut pri_buffer = agi_types::PixelBuffer::new(agi_types::PRI_BUFFER_BASE_COLOR);
+        agi_types::render_to_buffers(pic.get_instructions(), &agi_types::RenderOptions::default(), &mut Some(&mut pic_buffer),
+            &mut Some(&mut pri_buffer), &mut None).unwrap();
+
+        assert!(screens.picture == pic_buffer.get_pixel_indexes(), "Picture {} has a different picture screen in the library", i);
+        assert!(screens.priority == pri_buffer.get_pixel_indexes(), "Picture {} has a different priority screen in the library", i);
+    }
+}
+
+#[test]
+#[ignore]
+fn generate_reference_renders() {
+    fs::write(sample_pics_dir().join(REFERENCE_RENDERS_FILE), reference_renders()).unwrap();
+}
+
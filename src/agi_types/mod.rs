@@ -1,5 +0,0 @@
-pub mod common;
-pub mod pic;
-pub mod pic_render;
-pub mod resource;
-pub mod game;
@@ -0,0 +1,448 @@
+use std::{fs, path::{Path, PathBuf}};
+use clap::{Args, Parser, Subcommand};
+use serde_json::{json, Value};
+
+use agi_types::{common::*, game::*, pic::*, pic_render::*, pic_batch::*, pic_svg::*, pic_export::*, pic_upscale::*};
+
+#[derive(Parser)]
+#[command(name = "agi-pic-viewer", version, about = "View, render and export the pictures from Sierra AGI games")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Game directory to open in the viewer, the same as the view command.  Without one the viewer starts empty
+    pub game_dir : Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command : Option<Command>
+}
+
+/*
+Pictures are numbered by their position in the game's picture list, the same as the "PIC n" labels in the viewer.
+The list command shows which resource number each one is.
+*/
+#[derive(Subcommand)]
+pub enum Command {
+    /// Open a game in the viewer
+    View {
+        game_dir : PathBuf
+    },
+    /// Show a summary of a game's pictures
+    Info {
+        game_dir : PathBuf,
+        #[arg(long)]
+        json : bool
+    },
+    /// List every picture in a game
+    List {
+        game_dir : PathBuf,
+        #[arg(long)]
+        json : bool
+    },
+    /// Render a picture to a PNG, PPM, BMP or SVG file, chosen by the extension of the output
+    Render {
+        game_dir : PathBuf,
+        pic : usize,
+        #[arg(short, long)]
+        output : PathBuf,
+        /// Only render up to and including this instruction
+        #[arg(long)]
+        instruction : Option<usize>,
+        #[command(flatten)]
+        image : ImageArgs
+    },
+    /// Render every picture in a game into a directory
+    ExportAll {
+        game_dir : PathBuf,
+        #[arg(short, long)]
+        output_dir : PathBuf,
+        /// png, ppm or bmp
+        #[arg(long, default_value = "png")]
+        format : String,
+        /// Worker threads to render with, defaults to one per core
+        #[arg(long)]
+        threads : Option<usize>,
+        #[command(flatten)]
+        image : ImageArgs
+    },
+    /// Print the instructions of a picture along with their offsets and raw bytes
+    Disasm {
+        game_dir : PathBuf,
+        pic : usize,
+        #[arg(long)]
+        json : bool
+    },
+    /// Check every picture in a game for problems, exiting with an error if there are any
+    Validate {
+        game_dir : PathBuf,
+        #[arg(long)]
+        json : bool
+    },
+    /// Write a picture's instructions as JSON, in the schema from docs/pic-resource.schema.json
+    ToJson {
+        game_dir : PathBuf,
+        pic : usize,
+        /// Write to this file rather than standard output
+        #[arg(short, long)]
+        output : Option<PathBuf>
+    },
+    /// Encode a picture's JSON instructions back into PIC resource data
+    FromJson {
+        input : PathBuf,
+        #[arg(short, long)]
+        output : PathBuf
+    }
+}
+
+#[derive(Args)]
+pub struct ImageArgs {
+    /// Render the priority buffer rather than the picture
+    #[arg(long)]
+    priority : bool,
+    /// Double the width, as the pixels were shown on the original hardware
+    #[arg(long)]
+    stretch : bool,
+    /// Integer upscale, applied after the upscaler and stretch
+    #[arg(long, default_value_t = 1)]
+    scale : usize,
+    /// none, scale2x, scale3x, xbr or hq2x
+    #[arg(long, default_value = "none", value_parser = parse_upscaler)]
+    upscaler : Upscaler,
+    /// ega or greyscale
+    #[arg(long, default_value = "ega", value_parser = parse_palette)]
+    palette : Palette
+}
+
+impl ImageArgs {
+    fn to_export_options(&self, format : ImageFormat) -> ImageExportOptions {
+        ImageExportOptions { format, upscaler : self.upscaler, stretch : self.stretch, scale : self.scale, palette : self.palette }
+    }
+
+    fn buffer_type(&self) -> PictureBufferType {
+        if self.priority { PictureBufferType::Priority } else { PictureBufferType::Picture }
+    }
+}
+
+fn parse_upscaler(name : &str) -> Result<Upscaler, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "none" => Ok(Upscaler::None),
+        "scale2x" => Ok(Upscaler::Scale2x),
+        "scale3x" => Ok(Upscaler::Scale3x),
+        "xbr" => Ok(Upscaler::Xbr),
+        "hq2x" => Ok(Upscaler::Hq2x),
+        _ => Err(format!("unknown upscaler '{}'", name))
+    }
+}
+
+fn parse_palette(name : &str) -> Result<Palette, String> {
+    Palette::ALL.into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown palette '{}'", name))
+}
+
+// Runs any command other than View, which needs the GUI.  Returns false if the command found problems.
+pub fn run(command : Command) -> Result<bool, AgiError> {
+    match command {
+        Command::View { .. } => Err(AgiError::Parse(String::from("The view command is handled by the viewer"))),
+        Command::Info { game_dir, json } => info(&game_dir, json).map(|_| true),
+        Command::List { game_dir, json } => list(&game_dir, json).map(|_| true),
+        Command::Render { game_dir, pic, output, instruction, image } => render(&game_dir, pic, &output, instruction, &image).map(|_| true),
+        Command::ExportAll { game_dir, output_dir, format, threads, image } => export_all(&game_dir, &output_dir, &format, threads, &image),
+        Command::Disasm { game_dir, pic, json } => disasm(&game_dir, pic, json).map(|_| true),
+        Command::Validate { game_dir, json } => validate(&game_dir, json),
+        Command::ToJson { game_dir, pic, output } => to_json(&game_dir, pic, output.as_deref()).map(|_| true),
+        Command::FromJson { input, output } => from_json(&input, &output).map(|_| true)
+    }
+}
+
+fn print_json(value : &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+fn get_pic(game : &Game, pic : usize) -> Result<&PicResource, AgiError> {
+    game.pic_resources.get(pic).ok_or_else(|| AgiError::Parse(format!("There is no picture {}, the game only has {}", pic, game.pic_resources.len())))
+}
+
+fn info(game_dir : &Path, json : bool) -> Result<(), AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+
+    let instruction_count : usize = game.pic_resources.iter().map(|p| p.get_instructions().len()).sum();
+    let data_size : usize = game.all_resources.iter().map(|r| r.get_raw_data().len()).sum();
+
+    if json {
+        print_json(&json!({
+            "game_dir" : game.dir_name,
+            "pic_count" : game.pic_resources.len(),
+            "instruction_count" : instruction_count,
+            "data_size" : data_size
+        }));
+    } else {
+        println!("Game:         {}", game.dir_name);
+        println!("Pictures:     {}", game.pic_resources.len());
+        println!("Instructions: {}", instruction_count);
+        println!("Data size:    {} bytes", data_size);
+    }
+
+    Ok(())
+}
+
+fn list(game_dir : &Path, json : bool) -> Result<(), AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+
+    let pics : Vec<Value> = game.pic_resources.iter().zip(game.all_resources.iter()).enumerate().map(|(i, (pic, resource))| {
+        let mut entry = serde_json::to_value(resource).unwrap_or_default();
+        entry["pic"] = json!(i);
+        entry["instruction_count"] = json!(pic.get_instructions().len());
+        entry
+    }).collect();
+
+    if json {
+        print_json(&Value::Array(pics));
+    } else {
+        println!("{:>5} {:>8} {:>4} {:>8} {:>6} {:>12}", "PIC", "RESOURCE", "VOL", "OFFSET", "SIZE", "INSTRUCTIONS");
+        for pic in pics {
+            let field = |name : &str| pic[name].as_u64().unwrap_or_default();
+            println!("{:>5} {:>8} {:>4} {:>8} {:>6} {:>12}", field("pic"), field("resource_index"), field("vol_file"), format!("{:06x}", field("vol_file_offset")), field("size"), field("instruction_count"));
+        }
+    }
+
+    Ok(())
+}
+
+fn render(game_dir : &Path, pic : usize, output : &Path, instruction : Option<usize>, image : &ImageArgs) -> Result<(), AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+    let instructions = get_pic(&game, pic)?.get_instructions();
+
+    let last_instruction = instruction.unwrap_or(instructions.len().saturating_sub(1));
+    if last_instruction >= instructions.len() {
+        return Err(AgiError::Parse(format!("There is no instruction {}, picture {} only has {}", last_instruction, pic, instructions.len())));
+    }
+    let instructions = &instructions[0..=last_instruction];
+
+    let extension = output.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let data = if extension.eq_ignore_ascii_case("svg") {
+        instructions_to_svg(instructions, &SvgOptions { palette : image.palette, ..Default::default() })?.into_bytes()
+    } else {
+        let format = ImageFormat::from_extension(&extension)
+            .ok_or_else(|| AgiError::Parse(format!("Can't tell what format to write from the extension of {}", output.display())))?;
+
+        let mut buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        let (mut pic_buffer, mut pri_buffer) = match image.buffer_type() {
+            PictureBufferType::Picture => (Some(&mut buffer), None),
+            PictureBufferType::Priority => (None, Some(&mut buffer))
+        };
+        render_to_buffers(instructions, &RenderOptions::default(), &mut pic_buffer, &mut pri_buffer, &mut None)?;
+
+        export_buffer(&buffer, &image.to_export_options(format))?
+    };
+
+    fs::write(output, data)?;
+    Ok(())
+}
+
+fn export_all(game_dir : &Path, output_dir : &Path, format : &str, threads : Option<usize>, image : &ImageArgs) -> Result<bool, AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+    let format = ImageFormat::from_extension(format).ok_or_else(|| AgiError::Parse(format!("Unknown image format '{}'", format)))?;
+    let options = image.to_export_options(format);
+    let prefix = if image.priority { "pri" } else { "pic" };
+
+    fs::create_dir_all(output_dir)?;
+
+    let mut all_ok = true;
+    for rendered in render_batch(game.pic_resources.clone(), threads) {
+        let result = rendered.result.and_then(|rendered_pic| {
+            let buffer = if image.priority { &rendered_pic.pri_buffer } else { &rendered_pic.pic_buffer };
+            let path = output_dir.join(format!("{}_{:03}.{}", prefix, rendered.pic_index, format.extension()));
+            fs::write(&path, export_buffer(buffer, &options)?)?;
+            Ok(path)
+        });
+
+        match result {
+            Ok(path) => println!("{}", path.display()),
+            Err(err) => {
+                eprintln!("Failed to export picture {}: {:?}", rendered.pic_index, err);
+                all_ok = false;
+            }
+        }
+    }
+
+    Ok(all_ok)
+}
+
+fn disasm(game_dir : &Path, pic : usize, json : bool) -> Result<(), AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+    get_pic(&game, pic)?;
+    let raw_data = game.all_resources[pic].get_raw_data();
+
+    // Walk the raw data the same way PicResource::new does, so we know where each instruction came from
+    let mut instructions = vec![];
+    let mut offset = 0usize;
+    while offset < raw_data.len() {
+        let (instruction, next_offset) = DerivedPicRenderInstruction::create_from_vec(raw_data, offset)?;
+        instructions.push(json!({
+            "index" : instructions.len(),
+            "offset" : offset,
+            "bytes" : raw_data[offset..next_offset].to_vec(),
+            "opcode" : instruction.get_instruction().opcode().unwrap_or(raw_data[offset]),
+            "name" : instruction.get_instruction().to_string(),
+            "text" : instruction.to_string()
+        }));
+        offset = next_offset;
+    }
+
+    if json {
+        print_json(&Value::Array(instructions));
+    } else {
+        for instruction in instructions {
+            let bytes = instruction["bytes"].as_array().map(|b| b.iter().map(|b| format!("{:02X}", b.as_u64().unwrap_or_default())).collect::<Vec<String>>().join(" ")).unwrap_or_default();
+            println!("{:>5}  {:04X}  {}", instruction["index"].as_u64().unwrap_or_default(), instruction["offset"].as_u64().unwrap_or_default(), instruction["text"].as_str().unwrap_or_default());
+            println!("             {}", bytes);
+        }
+    }
+
+    Ok(())
+}
+
+fn to_json(game_dir : &Path, pic : usize, output : Option<&Path>) -> Result<(), AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+    let json = serde_json::to_string_pretty(get_pic(&game, pic)?)
+        .map_err(|e| AgiError::Export(e.to_string()))?;
+
+    match output {
+        Some(output) => fs::write(output, json)?,
+        None => println!("{}", json)
+    }
+
+    Ok(())
+}
+
+fn from_json(input : &Path, output : &Path) -> Result<(), AgiError> {
+    let pic : PicResource = serde_json::from_str(&fs::read_to_string(input)?)
+        .map_err(|e| AgiError::Parse(format!("{}: {}", input.display(), e)))?;
+
+    fs::write(output, pic.to_bytes()?)?;
+    Ok(())
+}
+
+// Problems that make a picture render wrongly are errors, anything merely unusual is a warning
+fn find_problems(raw_data : &[u8], pic : &PicResource) -> Vec<(&'static str, Option<usize>, String)> {
+    let mut problems = vec![];
+    let instructions = pic.get_instructions();
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            DerivedPicRenderInstruction::DrawLines(_, points) |
+            DerivedPicRenderInstruction::Fill(_, points) => {
+                if points.is_empty() {
+                    problems.push(("warning", Some(i), String::from("Has no points, or an odd number of coordinates")));
+                }
+                if let Some(point) = points.iter().find(|p| p.x as usize >= VIEWPORT_WIDTH || p.y as usize >= VIEWPORT_HEIGHT) {
+                    problems.push(("error", Some(i), format!("Point ({},{}) is outside the picture", point.x, point.y)));
+                }
+            },
+            DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::Unknown) => {
+                problems.push(("error", Some(i), String::from("Unknown instruction")));
+            },
+            DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::SetPenSizeAndStyle | PicRenderInstruction::PlotWithPen) => {
+                problems.push(("warning", Some(i), String::from("Pen instructions aren't supported yet, and will be skipped")));
+            },
+            DerivedPicRenderInstruction::Unimplemented(PicRenderInstruction::EndInstruction) if i != instructions.len() - 1 => {
+                problems.push(("warning", Some(i), String::from("Data after the end instruction")));
+            },
+            _ => ()
+        }
+    }
+
+    if raw_data.last() != Some(&0xFF) {
+        problems.push(("warning", None, String::from("Doesn't finish with an end instruction")));
+    }
+
+    if instructions.is_empty() {
+        problems.push(("error", None, String::from("Has no instructions")));
+    } else if let Err(err) = render_to_buffers(instructions, &RenderOptions::default(), &mut None, &mut None, &mut None) {
+        problems.push(("error", None, format!("Failed to render: {:?}", err)));
+    }
+
+    problems
+}
+
+fn validate(game_dir : &Path, json : bool) -> Result<bool, AgiError> {
+    let game = Game::new_from_dir(game_dir)?;
+
+    let mut error_count = 0;
+    let mut warning_count = 0;
+    let mut pics = vec![];
+
+    for (i, (pic, resource)) in game.pic_resources.iter().zip(game.all_resources.iter()).enumerate() {
+        let problems = find_problems(resource.get_raw_data(), pic);
+        error_count += problems.iter().filter(|(severity, _, _)| *severity == "error").count();
+        warning_count += problems.iter().filter(|(severity, _, _)| *severity == "warning").count();
+
+        if !json {
+            for (severity, instruction, message) in problems.iter() {
+                match instruction {
+                    Some(instruction) => println!("PIC {} (resource {}), instruction {}: {}: {}", i, resource.get_resource_index(), instruction, severity, message),
+                    None => println!("PIC {} (resource {}): {}: {}", i, resource.get_resource_index(), severity, message)
+                }
+            }
+        }
+
+        pics.push(json!({
+            "pic" : i,
+            "resource" : resource.get_resource_index(),
+            "problems" : problems.iter().map(|(severity, instruction, message)| json!({
+                "severity" : severity,
+                "instruction" : instruction,
+                "message" : message
+            })).collect::<Vec<Value>>()
+        }));
+    }
+
+    if json {
+        print_json(&json!({
+            "error_count" : error_count,
+            "warning_count" : warning_count,
+            "pics" : pics
+        }));
+    } else {
+        println!("{} pictures checked, {} errors, {} warnings", game.pic_resources.len(), error_count, warning_count);
+    }
+
+    Ok(error_count == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_pics_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("agi-types").join("sample_pics")
+    }
+
+    #[test]
+    fn arguments_parse() {
+        let cli = Cli::try_parse_from(["agi-pic-viewer", "render", "game", "3", "-o", "out.png", "--stretch", "--upscaler", "xbr", "--palette", "Greyscale"]).unwrap();
+        let Some(Command::Render { pic, image, .. }) = cli.command else { panic!("Expected a render command") };
+        assert_eq!(pic, 3);
+        assert!(image.stretch && !image.priority);
+        assert_eq!((image.upscaler, image.palette), (Upscaler::Xbr, Palette::GREYSCALE));
+
+        // A bare game directory still opens the viewer
+        let cli = Cli::try_parse_from(["agi-pic-viewer", "game"]).unwrap();
+        assert!(cli.command.is_none() && cli.game_dir == Some(PathBuf::from("game")));
+
+        assert!(Cli::try_parse_from(["agi-pic-viewer", "render", "game", "3", "-o", "out.png", "--upscaler", "blur"]).is_err());
+    }
+
+    #[test]
+    fn sample_pics_validate() {
+        let game = Game::new_from_dir(&sample_pics_dir()).unwrap();
+        for (i, (pic, resource)) in game.pic_resources.iter().zip(game.all_resources.iter()).enumerate() {
+            let problems = find_problems(resource.get_raw_data(), pic);
+            assert!(problems.iter().all(|(severity, _, _)| *severity != "error"), "PIC {}: {:?}", i, problems);
+        }
+
+        let broken = [0xF0, 0x01, 0xF6, 10, 10, 200, 10, 0xFC];
+        let problems = find_problems(&broken, &PicResource::new(&broken).unwrap());
+        assert_eq!(problems.iter().map(|(severity, instruction, _)| (*severity, *instruction)).collect::<Vec<_>>(),
+            vec![("error", Some(1)), ("error", Some(2)), ("warning", None)]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::path::Path;
+use agi_types::{common::*, game::*, pic_render::*};
+use eframe::egui;
+use egui::*;
+
+use crate::{AgiViewerApp, CanvasView};
+use crate::open_game::GameBrowser;
+
+// Space between the two halves of a split canvas
+const SPLIT_GAP : f32 = 4.0;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ComparedPic {
+    // The picture with the same resource number as the selected one, which is the same room in another release
+    SameResource,
+    // A picture picked by hand, by its position in the other game's list
+    Chosen(usize)
+}
+
+// Other games opened alongside the main one, e.g. another release of it, with one of them drawn beside the picture
+pub struct Comparison {
+    pub games : Vec<Game>,
+    // The game on the right of the canvas, None to show the picture on its own
+    pub selected_game : Option<usize>,
+    pub pic : ComparedPic,
+    // Draw the other picture up to the same instruction as this one, rather than all of it
+    pub sync_stepping : bool,
+    pub show_differences : bool,
+    pub shapes : Vec<Shape>,
+    difference_count : Option<usize>,
+    texture : Option<TextureHandle>,
+    difference_texture : Option<TextureHandle>
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Comparison {
+            games : vec![],
+            selected_game : None,
+            pic : ComparedPic::SameResource,
+            sync_stepping : true,
+            show_differences : false,
+            shapes : vec![],
+            difference_count : None,
+            texture : None,
+            difference_texture : None
+        }
+    }
+}
+
+impl AgiViewerApp {
+    pub fn add_compare_game(&mut self, game_dir : &Path) {
+        match Game::new_from_dir(game_dir) {
+            Ok(game) => {
+                self.add_recent_game(game_dir);
+                self.comparison.games.push(game);
+                self.comparison.selected_game = Some(self.comparison.games.len() - 1);
+                self.game_browser = None;
+                self.open_status = None;
+                self.canvas_view_rect = Rect::NOTHING;
+            },
+            Err(err) => self.open_status = Some(format!("Couldn't open {}: {:?}", game_dir.display(), err))
+        }
+    }
+
+    fn remove_compare_game(&mut self, index : usize) {
+        self.comparison.games.remove(index);
+        self.comparison.selected_game = match self.comparison.selected_game {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected
+        };
+        self.canvas_view_rect = Rect::NOTHING;
+    }
+
+    // The game being compared against and the number of its picture to show, if it has one to match this picture
+    fn get_compared_pic(&self) -> Option<(&Game, usize)> {
+        let game = &self.comparison.games[self.comparison.selected_game?];
+        let pic_index = match self.comparison.pic {
+            ComparedPic::SameResource => {
+                let resource_index = self.game.all_resources.get(self.selected_pic)?.get_resource_index();
+                game.all_resources.iter().position(|resource| resource.get_resource_index() == resource_index)?
+            },
+            ComparedPic::Chosen(pic_index) => pic_index
+        };
+
+        (pic_index < game.pic_resources.len()).then_some((game, pic_index))
+    }
+
+    // The part of the canvas for this game's picture, and the part for the other game's when comparing
+    pub fn split_canvas(&self, canvas : Rect) -> (Rect, Option<Rect>) {
+        if self.comparison.selected_game.is_none() {
+            return (canvas, None);
+        }
+
+        let half_width = (canvas.width() - SPLIT_GAP) / 2.0;
+        let left = Rect::from_min_size(canvas.min, vec2(half_width, canvas.height()));
+        let right = Rect::from_min_size(pos2(canvas.max.x - half_width, canvas.min.y), vec2(half_width, canvas.height()));
+        (left, Some(right))
+    }
+
+    /*
+    Renders the other game's picture into compare_view, the same place in its half of the canvas as view is in this
+    one, so zooming and panning keep them lined up.  The differences are worked out from whichever buffer is on show
+    and drawn over both pictures.
+    */
+    pub fn generate_comparison(&mut self, ctx : &egui::Context, view : Rect, compare_view : Rect, painter : &Painter) {
+        self.comparison.shapes.clear();
+        self.comparison.difference_count = None;
+
+        let Some((game, pic_index)) = self.get_compared_pic() else {
+            return;
+        };
+        let instructions = game.pic_resources[pic_index].get_instructions();
+        let Some(last_instruction) = instructions.len().checked_sub(1) else {
+            return;
+        };
+
+        // Stepping together draws the other picture up to the same instruction number, or all of it if it's shorter
+        let (last_instruction, sub_items) = match self.comparison.sync_stepping {
+            true if self.selected_instruction <= last_instruction => (self.selected_instruction, self.get_render_options().last_instruction_sub_items),
+            _ => (last_instruction, None)
+        };
+        let render_options = RenderOptions { last_instruction_sub_items : sub_items, ..self.render_options };
+
+        let (mut pic_buffer, mut pri_buffer) = (PixelBuffer::new(PIC_BUFFER_BASE_COLOR), PixelBuffer::new(PRI_BUFFER_BASE_COLOR));
+        let mut pic_vectors = (self.selected_canvas_view == CanvasView::PicBufferVectors).then(ShapeBuffer::new);
+        if let Err(err) = render_to_buffers(&instructions[0..=last_instruction], &render_options, &mut Some(&mut pic_buffer), &mut Some(&mut pri_buffer), &mut pic_vectors.as_mut()) {
+            println!("Error rendering PIC {} from {}: {:?}", pic_index, game.dir_name, err);
+        }
+
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        let shown_buffer = if self.selected_canvas_view == CanvasView::PriBufferPixels { &pri_buffer } else { &pic_buffer };
+        match pic_vectors {
+            Some(vectors) => {
+                self.comparison.shapes.push(Shape::rect_filled(compare_view, Rounding::none(), Color32::WHITE));
+                self.comparison.shapes.extend(Self::draw_vectors(&compare_view, self.line_width, &vectors, &self.palette, painter));
+            },
+            None => {
+                let image = self.get_palette_pixels(shown_buffer);
+                let texture = self.comparison.texture.get_or_insert_with(|| ctx.load_texture("COMPARE_BUFFER", image.clone(), TextureOptions::NEAREST));
+                texture.set(image, TextureOptions::NEAREST);
+                self.comparison.shapes.push(Shape::image(texture.id(), compare_view, uv, Color32::WHITE));
+            }
+        }
+
+        let Some(buffers) = &self.inspected_buffers else {
+            return;
+        };
+        let this_buffer = if self.selected_canvas_view == CanvasView::PriBufferPixels { &buffers.pri_buffer } else { &buffers.pic_buffer };
+        let differences = this_buffer.get_differences(shown_buffer);
+        self.comparison.difference_count = Some(differences.iter().filter(|different| **different).count());
+
+        if self.comparison.show_differences {
+            let pixels = differences.iter().map(|different| if *different { Color32::from_rgba_unmultiplied(0xFF, 0x00, 0x00, 0xA0) } else { Color32::TRANSPARENT }).collect();
+            let mask = ColorImage { size : [VIEWPORT_WIDTH, VIEWPORT_HEIGHT], pixels };
+            let texture = self.comparison.difference_texture.get_or_insert_with(|| ctx.load_texture("DIFFERENCES", mask.clone(), TextureOptions::NEAREST));
+            texture.set(mask, TextureOptions::NEAREST);
+            self.canvas_view_shapes.push(Shape::image(texture.id(), view, uv, Color32::WHITE));
+            self.comparison.shapes.push(Shape::image(texture.id(), compare_view, uv, Color32::WHITE));
+        }
+    }
+
+    // Draws the rendered comparison, with a caption saying what it is
+    pub fn draw_comparison(&self, compare_canvas : Rect, painter : &Painter) {
+        painter.extend(self.comparison.shapes.clone());
+
+        let caption = match (self.comparison.selected_game, self.get_compared_pic()) {
+            (_, Some((game, pic_index))) => {
+                let differences = self.comparison.difference_count.map_or(String::new(), |count| format!(", {} pixels differ", count));
+                format!("{} PIC {}{}", game.dir_name, pic_index, differences)
+            },
+            (Some(selected_game), None) => format!("{} has no matching picture", self.comparison.games[selected_game].dir_name),
+            (None, None) => return
+        };
+
+        let galley = painter.layout_no_wrap(caption, FontId::proportional(14.0), Color32::WHITE);
+        let caption_rect = Rect::from_min_size(compare_canvas.min, galley.size()).expand(3.0).translate(vec2(3.0, 3.0));
+        painter.rect_filled(caption_rect, Rounding::same(2.0), Color32::from_black_alpha(180));
+        painter.galley(compare_canvas.min + vec2(6.0, 6.0), galley);
+    }
+
+    pub fn compare_menu_ui(&mut self, ui : &mut Ui) {
+        ui.set_width(250f32);
+        let mut changed = false;
+
+        ui.strong("Compare With");
+        changed |= ui.radio_value(&mut self.comparison.selected_game, None, "Nothing").clicked();
+        let mut removed = None;
+        for (i, game) in self.comparison.games.iter().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.radio_value(&mut self.comparison.selected_game, Some(i), game.dir_name.as_str()).clicked();
+                if ui.small_button("✖").on_hover_text("Close this game").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(removed) = removed {
+            self.remove_compare_game(removed);
+        }
+
+        if ui.button("Add game…").clicked() {
+            let start_dir = Path::new(&self.game.dir_name).parent().map(Path::to_path_buf).unwrap_or_default();
+            let mut browser = GameBrowser::new(&start_dir);
+            browser.compare = true;
+            self.game_browser = Some(browser);
+            ui.close_menu();
+        }
+        let mut added = None;
+        ui.menu_button("Add recent game", |ui| {
+            for game_dir in &self.recent_games {
+                if ui.button(game_dir.to_string_lossy()).clicked() {
+                    added = Some(game_dir.clone());
+                    ui.close_menu();
+                }
+            }
+        });
+        if let Some(game_dir) = added {
+            self.add_compare_game(&game_dir);
+        }
+
+        ui.separator();
+        ui.strong("Picture");
+        changed |= ui.radio_value(&mut self.comparison.pic, ComparedPic::SameResource, "Same resource number").clicked();
+        if let Some(selected_game) = self.comparison.selected_game {
+            let pic_count = self.comparison.games[selected_game].pic_resources.len();
+            let mut chosen = match self.comparison.pic {
+                ComparedPic::Chosen(pic_index) => pic_index,
+                ComparedPic::SameResource => self.selected_pic
+            };
+            ui.horizontal(|ui| {
+                changed |= ui.radio_value(&mut self.comparison.pic, ComparedPic::Chosen(chosen), "PIC").clicked();
+                if pic_count > 0 && ui.add(DragValue::new(&mut chosen).clamp_range(0..=pic_count - 1)).changed() {
+                    self.comparison.pic = ComparedPic::Chosen(chosen);
+                    changed = true;
+                }
+            });
+        }
+
+        ui.separator();
+        changed |= ui.checkbox(&mut self.comparison.sync_stepping, "Step through both together").clicked();
+        changed |= ui.checkbox(&mut self.comparison.show_differences, "Show differences").clicked();
+
+        if changed {
+            self.canvas_view_rect = Rect::NOTHING;
+        }
+    }
+}
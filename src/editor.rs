@@ -0,0 +1,350 @@
+use agi_types::{common::*, pic::*, pic_render::*, pic_edit::*};
+use eframe::egui;
+use egui::*;
+
+use crate::AgiViewerApp;
+
+// How close in screen pixels the pointer has to be to grab a point
+const POINT_GRAB_RADIUS : f32 = 8.0;
+const POINT_MARKER_SIZE : f32 = 6.0;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum EditTool {
+    // Hovering and clicking inspects pixels, the viewer as it's always been
+    Inspect,
+    MovePoints,
+    // Each click adds a point to a line or fill of this kind
+    Draw(PicRenderInstruction)
+}
+
+impl EditTool {
+    pub const ALL : [EditTool ; 7] = [
+        EditTool::Inspect,
+        EditTool::MovePoints,
+        EditTool::Draw(PicRenderInstruction::AbsLine),
+        EditTool::Draw(PicRenderInstruction::RelLine),
+        EditTool::Draw(PicRenderInstruction::DrawXCorner),
+        EditTool::Draw(PicRenderInstruction::DrawYCorner),
+        EditTool::Draw(PicRenderInstruction::Fill)
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditTool::Inspect => "Inspect",
+            EditTool::MovePoints => "Move points",
+            EditTool::Draw(PicRenderInstruction::AbsLine) => "Line",
+            EditTool::Draw(PicRenderInstruction::RelLine) => "Short line",
+            EditTool::Draw(PicRenderInstruction::DrawXCorner) => "X corner",
+            EditTool::Draw(PicRenderInstruction::DrawYCorner) => "Y corner",
+            EditTool::Draw(_) => "Fill"
+        }
+    }
+}
+
+impl AgiViewerApp {
+    // Applies an edit to the selected picture so it can be undone
+    fn edit_selected_pic(&mut self, edit : impl FnOnce(&mut Vec<DerivedPicRenderInstruction>)) {
+        let pic = &mut self.game.pic_resources[self.selected_pic];
+        self.edit_histories.entry(self.selected_pic).or_default().checkpoint(pic);
+        edit(pic.get_instructions_mut());
+
+        self.picture_edited();
+    }
+
+    // Brings everything made from the selected picture up to date with its instructions
+    pub fn picture_edited(&mut self) {
+        let instruction_count = self.get_selected_pic().get_instructions().len();
+        self.selected_instruction = self.selected_instruction.min(instruction_count.saturating_sub(1));
+        self.selected_sub_item = None;
+        self.playing = false;
+
+        self.update_render_snapshots();
+        self.update_thumbnail(self.selected_pic);
+        self.unsaved_pics.insert(self.selected_pic);
+        self.canvas_view_rect = Rect::NOTHING;
+    }
+
+    // Writes the edited pictures back into the game's PICDIR and VOL files
+    pub fn save_pics(&mut self) {
+        // Nothing is written, so there are no backups to mention either
+        if self.unsaved_pics.is_empty() {
+            return;
+        }
+
+        let mut pic_indexes : Vec<usize> = self.unsaved_pics.iter().copied().collect();
+        pic_indexes.sort();
+
+        let mut saved = 0;
+        for pic_index in pic_indexes {
+            if let Err(err) = self.game.save_pic(pic_index) {
+                self.save_status = Some(format!("Saving PIC {} failed: {:?}", pic_index, err));
+                return;
+            }
+            self.unsaved_pics.remove(&pic_index);
+            saved += 1;
+        }
+
+        self.save_status = Some(format!("Saved {} pictures to {}, the original files are kept as .bak", saved, self.game.dir_name));
+    }
+
+    fn update_thumbnail(&mut self, pic_index : usize) {
+        let mut pic_buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        if render_to_buffers(self.game.pic_resources[pic_index].get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut None, &mut None).is_ok() {
+            let image_data = ColorImage { size : [VIEWPORT_WIDTH, VIEWPORT_HEIGHT], pixels : pic_buffer.get_pixels_vec() };
+            if let Some(thumbnail) = self.thumbnail_texture_handles.get_mut(pic_index) {
+                thumbnail.set(image_data, Default::default());
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        let pic = &mut self.game.pic_resources[self.selected_pic];
+        if self.edit_histories.entry(self.selected_pic).or_default().undo(pic) {
+            self.drawing_instruction = None;
+            self.picture_edited();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let pic = &mut self.game.pic_resources[self.selected_pic];
+        if self.edit_histories.entry(self.selected_pic).or_default().redo(pic) {
+            self.drawing_instruction = None;
+            self.picture_edited();
+        }
+    }
+
+    fn insert_instruction(&mut self, instruction : DerivedPicRenderInstruction) -> usize {
+        let index = get_insert_index(self.get_selected_pic().get_instructions(), self.selected_instruction);
+        self.edit_selected_pic(|instructions| instructions.insert(index, instruction));
+        self.select_instruction(index, None);
+        self.scroll_to_selected_instruction = true;
+        index
+    }
+
+    fn delete_selected_instruction(&mut self) {
+        let index = self.selected_instruction;
+        if can_delete_instruction(self.get_selected_pic().get_instructions(), index) {
+            self.drawing_instruction = None;
+            self.edit_selected_pic(|instructions| { instructions.remove(index); });
+            self.select_instruction(index.saturating_sub(1), None);
+        }
+    }
+
+    // Swaps the selected instruction with the one before or after it
+    fn move_selected_instruction(&mut self, up : bool) {
+        let index = self.selected_instruction;
+        if let Some(other) = get_move_index(self.get_selected_pic().get_instructions(), index, up) {
+            self.drawing_instruction = None;
+            self.edit_selected_pic(|instructions| instructions.swap(index, other));
+            self.select_instruction(other, None);
+            self.scroll_to_selected_instruction = true;
+        }
+    }
+
+    // Adds a point to the line or fill being drawn, starting a new one after the selected instruction if there isn't one
+    fn add_point(&mut self, kind : PicRenderInstruction, point : PosU8) {
+        let selected = self.selected_instruction;
+        let continuing = self.drawing_instruction == Some(selected)
+            && *self.get_selected_pic().get_instructions()[selected].get_instruction() == kind;
+
+        if continuing {
+            self.edit_selected_pic(|instructions| {
+                if let DerivedPicRenderInstruction::DrawLines(_, points) | DerivedPicRenderInstruction::Fill(_, points) = &mut instructions[selected] {
+                    points.push(constrain_next_point(kind, points, point));
+                }
+            });
+            self.select_instruction(selected, None);
+        } else {
+            let instruction = match kind {
+                PicRenderInstruction::Fill => DerivedPicRenderInstruction::Fill(kind, vec![point]),
+                _ => DerivedPicRenderInstruction::DrawLines(kind, vec![point])
+            };
+            self.drawing_instruction = Some(self.insert_instruction(instruction));
+        }
+    }
+
+    fn get_selected_points(&self) -> &[PosU8] {
+        match self.get_selected_pic().get_instructions().get(self.selected_instruction) {
+            Some(DerivedPicRenderInstruction::DrawLines(_, points) | DerivedPicRenderInstruction::Fill(_, points)) => points,
+            _ => &[]
+        }
+    }
+
+    // The screen position of the middle of an AGI pixel
+    fn get_point_pos(view : &Rect, point : PosU8) -> Pos2 {
+        let (x_step, y_step) = Self::get_xy_step(view);
+        pos2(view.min.x + (point.x as f32 + 0.5) * x_step, view.min.y + (point.y as f32 + 0.5) * y_step)
+    }
+
+    // Starts, continues and finishes dragging a point of the selected instruction, returning true if the drag was used
+    // for that rather than panning
+    pub fn handle_edit_drag(&mut self, response : &Response, view : &Rect) -> bool {
+        if self.edit_tool != EditTool::MovePoints {
+            return false;
+        }
+
+        if response.drag_started() {
+            // Where the button went down, the drag only starts once the pointer has moved a little way from there
+            let pointer = response.ctx.input().pointer.press_origin().unwrap_or_default();
+            self.dragged_point = self.get_selected_points().iter()
+                .position(|point| Self::get_point_pos(view, *point).distance(pointer) <= POINT_GRAB_RADIUS)
+                .map(|point_index| (point_index, self.get_selected_pic().get_instructions().clone()));
+        }
+
+        let Some(point_index) = self.dragged_point.as_ref().map(|(point_index, _)| *point_index) else {
+            return false;
+        };
+
+        if response.drag_released() {
+            let (_, before) = self.dragged_point.take().unwrap();
+            if before != *self.get_selected_pic().get_instructions() {
+                self.edit_histories.entry(self.selected_pic).or_default().checkpoint_instructions(before);
+                self.picture_edited();
+            } else {
+                // Dropped where it was picked up, so there's nothing to undo or save, only the snapshots to put back
+                self.update_render_snapshots();
+                self.canvas_view_rect = Rect::NOTHING;
+            }
+        } else if let Some((x, y)) = response.interact_pointer_pos().and_then(|pos| Self::get_canvas_pixel(view, pos)) {
+            // Only the view is redrawn while dragging, the snapshots and thumbnail wait for the drop
+            let instruction = &mut self.game.pic_resources[self.selected_pic].get_instructions_mut()[self.selected_instruction];
+            move_point(instruction, point_index, PosU8::new(x as u8, y as u8));
+            self.render_snapshots = None;
+            self.canvas_view_rect = Rect::NOTHING;
+        }
+
+        true
+    }
+
+    pub fn handle_edit_click(&mut self, response : &Response, x : usize, y : usize) {
+        if let EditTool::Draw(kind) = self.edit_tool {
+            if response.clicked() {
+                self.add_point(kind, PosU8::new(x as u8, y as u8));
+            } else if response.secondary_clicked() {
+                // Right click finishes the line or fill, the next click starts a new one
+                self.drawing_instruction = None;
+            }
+        }
+    }
+
+    // Marks the points of the selected instruction, the ones the move tool can grab
+    pub fn draw_edit_points(&self, view : &Rect, painter : &Painter) {
+        if self.edit_tool == EditTool::Inspect {
+            return;
+        }
+
+        for (index, point) in self.get_selected_points().iter().enumerate() {
+            let color = if self.dragged_point.as_ref().is_some_and(|(dragged, _)| *dragged == index) { Color32::from_rgb(0xFF, 0x00, 0xFF) } else { Color32::WHITE };
+            let marker = Rect::from_center_size(Self::get_point_pos(view, *point), Vec2::splat(POINT_MARKER_SIZE));
+            painter.rect_filled(marker, Rounding::none(), color);
+            painter.rect_stroke(marker, Rounding::none(), Stroke::new(1.0, Color32::BLACK));
+        }
+    }
+
+    pub fn handle_edit_keys(&mut self, ctx : &egui::Context) {
+        if ctx.wants_keyboard_input() || self.game.pic_resources.is_empty() {
+            return;
+        }
+
+        let input = ctx.input();
+        let command = input.modifiers.command;
+        let (undo, redo) = (command && input.key_pressed(Key::Z) && !input.modifiers.shift, command && (input.key_pressed(Key::Y) || (input.key_pressed(Key::Z) && input.modifiers.shift)));
+        let (delete, escape) = (input.key_pressed(Key::Delete), input.key_pressed(Key::Escape));
+        let save = command && input.key_pressed(Key::S);
+        drop(input);
+
+        if save {
+            self.save_pics();
+        }
+        if undo {
+            self.undo();
+        }
+        if redo {
+            self.redo();
+        }
+        if delete {
+            self.delete_selected_instruction();
+        }
+        if escape {
+            self.drawing_instruction = None;
+        }
+    }
+
+    fn color_menu(ui : &mut Ui, label : &str, palette : &Palette) -> Option<Option<u8>> {
+        let mut chosen = None;
+        ui.menu_button(label, |ui| {
+            if ui.button("Off").clicked() {
+                chosen = Some(None);
+                ui.close_menu();
+            }
+            for color in 0..16u8 {
+                let text = RichText::new(format!("■ {}", get_color_str(color))).color(palette.get_color(color));
+                if ui.button(text).clicked() {
+                    chosen = Some(Some(color));
+                    ui.close_menu();
+                }
+            }
+        });
+        chosen
+    }
+
+    pub fn editor_ui(&mut self, ui : &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for tool in EditTool::ALL {
+                if ui.selectable_value(&mut self.edit_tool, tool, tool.name()).clicked() {
+                    self.drawing_instruction = None;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if let Some(color) = Self::color_menu(ui, "Picture colour", &self.palette) {
+                let instruction = match color {
+                    Some(_) => PicRenderInstruction::SetPicColorAndEnablePicDraw,
+                    None => PicRenderInstruction::DisablePicDraw
+                };
+                self.insert_instruction(DerivedPicRenderInstruction::SetColor(instruction, PictureBufferType::Picture, color));
+            }
+            if let Some(color) = Self::color_menu(ui, "Priority colour", &self.palette) {
+                let instruction = match color {
+                    Some(_) => PicRenderInstruction::SetPriColorAndEnablePriDraw,
+                    None => PicRenderInstruction::DisablePriDraw
+                };
+                self.insert_instruction(DerivedPicRenderInstruction::SetColor(instruction, PictureBufferType::Priority, color));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let history = self.edit_histories.get(&self.selected_pic);
+            let (can_undo, can_redo) = (history.is_some_and(|h| h.can_undo()), history.is_some_and(|h| h.can_redo()));
+            let (instructions, selected) = (self.get_selected_pic().get_instructions(), self.selected_instruction);
+            let (can_move_up, can_move_down) = (get_move_index(instructions, selected, true).is_some(), get_move_index(instructions, selected, false).is_some());
+            let can_delete = can_delete_instruction(instructions, selected);
+
+            if ui.add_enabled(can_undo, Button::new("↶")).on_hover_text("Undo (Ctrl+Z)").clicked() {
+                self.undo();
+            }
+            if ui.add_enabled(can_redo, Button::new("↷")).on_hover_text("Redo (Ctrl+Y)").clicked() {
+                self.redo();
+            }
+            ui.separator();
+            if ui.add_enabled(can_move_up, Button::new("⬆")).on_hover_text("Move the instruction up").clicked() {
+                self.move_selected_instruction(true);
+            }
+            if ui.add_enabled(can_move_down, Button::new("⬇")).on_hover_text("Move the instruction down").clicked() {
+                self.move_selected_instruction(false);
+            }
+            if ui.add_enabled(can_delete, Button::new("🗑")).on_hover_text("Delete the instruction (Delete)").clicked() {
+                self.delete_selected_instruction();
+            }
+            ui.separator();
+            if ui.add_enabled(!self.unsaved_pics.is_empty(), Button::new("💾 Save")).on_hover_text("Save the edited pictures into the game (Ctrl+S)").clicked() {
+                self.save_pics();
+            }
+        });
+
+        if let Some(save_status) = &self.save_status {
+            ui.label(save_status);
+        }
+    }
+}
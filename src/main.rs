@@ -1,11 +1,18 @@
-use std::{{fs::File}, io::{Read}, path::{Path}, env};
-use agi_types::pic_render::*;
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, sync::mpsc::{Receiver, TryRecvError}};
+use clap::Parser;
+use cli::{Cli, Command};
+use agi_types::{common::*, pic::*, game::*, pic_render::*, pic_edit::*, pic_batch::*, pic_svg::*, pic_export::*, pic_upscale::*};
 use eframe::egui;
 use egui::*;
 use egui::style::*;
+use editor::EditTool;
+use open_game::GameBrowser;
+use compare::Comparison;
 
-use crate::agi_types::{common::*, resource::*, pic::*, game::*};
-mod agi_types;
+mod cli;
+mod compare;
+mod editor;
+mod open_game;
 
 #[derive(PartialEq)]
 enum CanvasView {
@@ -14,103 +21,536 @@ enum CanvasView {
     PicBufferVectors,
 }
 
+#[derive(PartialEq, Clone, Copy)]
+enum CanvasFit {
+    // Fill the whole canvas
+    Stretch,
+    // As large as fits with the 2:1 pixels the games were shown with
+    AspectCorrect
+}
+
+// The scale where the pixel grid starts being drawn, any smaller and it hides the picture
+const PIXEL_GRID_MIN_SCALE : f32 = 8.0;
+// Playback speed in steps per second
+const MIN_PLAYBACK_SPEED : f32 = 1.0;
+const MAX_PLAYBACK_SPEED : f32 = 500.0;
+
+const MIN_CANVAS_SCALE : f32 = 0.25;
+const MAX_CANVAS_SCALE : f32 = 64.0;
+
+// The picture and priority buffers behind the canvas, kept for inspecting pixels
+struct InspectedBuffers {
+    pic_buffer : PixelBuffer,
+    pri_buffer : PixelBuffer
+}
+
+// The pixels and vectors to draw on the canvas, and the buffers they came from
+type GeneratedView = (Option<ColorImage>, Option<Vec<Shape>>, InspectedBuffers);
+
 struct AgiViewerApp {
-    _pointer_loc : Pos2,
     game : Game,
+    // Where the picture was when canvas_view_shapes were made, and the canvas it's drawn on
     canvas_view_rect : Rect,
+    canvas_rect : Rect,
+    // While there's a fit the picture follows the canvas size, zooming or panning switches to the scale and offset
+    canvas_fit : Option<CanvasFit>,
+    canvas_scale : Vec2,
+    canvas_offset : Vec2,
+    show_pixel_grid : bool,
     canvas_view_shapes : Vec<Shape>,
     selected_pic : usize,
     thumbnail_texture_handles : Vec<TextureHandle>,
     main_viewport_texture : Option<TextureHandle>,
+    highlight_texture : Option<TextureHandle>,
     selected_canvas_view : CanvasView,
     selected_instruction : usize,
+    // The last of the selected instruction's lines or fill points to draw, None to draw all of it
+    selected_sub_item : Option<usize>,
+    scroll_to_selected_instruction : bool,
+    playing : bool,
+    playback_speed : f32,
+    playback_sub_items : bool,
+    playback_time : f32,
+    inspected_buffers : Option<InspectedBuffers>,
+    edit_tool : EditTool,
+    // Undo for each picture that's been edited, by picture number
+    edit_histories : HashMap<usize, EditHistory>,
+    // The line or fill that clicks with a drawing tool add points to
+    drawing_instruction : Option<usize>,
+    // The point being moved, and the instructions from before the drag so it can be undone if it changed anything
+    dragged_point : Option<(usize, Vec<DerivedPicRenderInstruction>)>,
+    // Pictures edited since they were last saved into the game
+    unsaved_pics : HashSet<usize>,
+    save_status : Option<String>,
+    // Most recently opened first, kept between sessions
+    recent_games : Vec<PathBuf>,
+    game_browser : Option<GameBrowser>,
+    // A game waiting to be opened until the unsaved edits are dealt with
+    pending_open : Option<PathBuf>,
+    open_status : Option<String>,
+    window_title_changed : bool,
+    comparison : Comparison,
     show_pixel_underlay : bool,
     line_width : f32,
     new_line_width : f32,
-    render_options : RenderOptions
-}
-
-enum RenderData {
-    Pixel(Vec<Color32>),
-    Vector(Vec<Shape>),
-    VectorAndPixel(Vec<Shape>, Vec<Color32>)
-
+    render_options : RenderOptions,
+    render_snapshots : Option<RenderSnapshots>,
+    // Why the selected picture couldn't be drawn, shown on the canvas in its place
+    render_error : Option<String>,
+    thumbnail_renders : Option<Receiver<BatchRenderResult>>,
+    palette : Palette,
+    upscaler : Upscaler,
+    export_dir : String,
+    export_status : Option<String>,
+    image_export_options : ImageExportOptions,
+    animation_export_options : AnimationExportOptions
 }
 
 impl AgiViewerApp {
-    fn new(game : Game) -> Self {
-        let selected_instruction = if game.pic_resources.is_empty() { 0 } else { game.pic_resources[0].get_instructions().len() - 1 };
-        AgiViewerApp {
-            _pointer_loc : Pos2::default(),
+    fn new(game : Game, recent_games : Vec<PathBuf>) -> Self {
+        let selected_instruction = game.pic_resources.first().map_or(0, |pic| pic.get_instructions().len().saturating_sub(1));
+        let mut app = AgiViewerApp {
             game,
             canvas_view_rect : Rect::EVERYTHING,
+            canvas_rect : Rect::NOTHING,
+            canvas_fit : Some(CanvasFit::Stretch),
+            canvas_scale : vec2(1.0, 1.0),
+            canvas_offset : Vec2::ZERO,
+            show_pixel_grid : false,
             canvas_view_shapes : vec![],
             selected_pic : 0,
             thumbnail_texture_handles : vec![],
             main_viewport_texture : None,
+            highlight_texture : None,
             selected_canvas_view : CanvasView::PicBufferPixels,
             selected_instruction,
+            selected_sub_item : None,
+            scroll_to_selected_instruction : false,
+            playing : false,
+            playback_speed : 20.0,
+            playback_sub_items : false,
+            playback_time : 0.0,
+            inspected_buffers : None,
+            edit_tool : EditTool::Inspect,
+            edit_histories : HashMap::new(),
+            drawing_instruction : None,
+            dragged_point : None,
+            unsaved_pics : HashSet::new(),
+            save_status : None,
+            recent_games,
+            game_browser : None,
+            pending_open : None,
+            open_status : None,
+            window_title_changed : false,
+            comparison : Comparison::default(),
             show_pixel_underlay : false,
             line_width : 2.0,
             new_line_width : 2.0,
-            render_options : RenderOptions { render_only_selected_instruction: false, show_fill_outlines: false }
-        }
+            render_options : RenderOptions::default(),
+            render_snapshots : None,
+            render_error : None,
+            thumbnail_renders : None,
+            palette : Palette::default(),
+            upscaler : Upscaler::None,
+            export_dir : String::from("exports"),
+            export_status : None,
+            image_export_options : ImageExportOptions::default(),
+            animation_export_options : AnimationExportOptions::default()
+        };
+
+        app.update_render_snapshots();
+        app
     }
 
     fn get_selected_pic(&self) -> &PicResource{
         &self.game.pic_resources[self.selected_pic]
     }
 
-    fn generate_view(&self, pic : &PicResource, view : Rect, canvas_view_type : &CanvasView, line_width : f32, painter : &Painter) -> (Option<Vec<Color32>>, Option<Vec<Shape>>) {
-        // The actual image pixels
-        let (mut pic_buffer, mut pri_buffer, mut pic_vectors) = match canvas_view_type {
-            CanvasView::PicBufferPixels => {
-                (Some(PixelBuffer::new(get_color(PIC_BUFFER_BASE_COLOR))), None, None)
-            },
-            CanvasView::PriBufferPixels => {
-                (None, Some(PixelBuffer::new(get_color(PIC_BUFFER_BASE_COLOR))), None)
+    fn select_instruction(&mut self, instruction : usize, sub_item : Option<usize>) {
+        self.selected_instruction = instruction;
+        self.selected_sub_item = sub_item;
+
+        // Invalidates the view for redraw
+        self.canvas_view_rect = Rect::NOTHING;
+    }
+
+    // A picture can have no instructions at all, in which case this is the same as the first
+    fn select_last_instruction(&mut self) {
+        self.select_instruction(self.get_selected_pic().get_instructions().len().saturating_sub(1), None);
+    }
+
+    // The selected instruction's parts, if stepping through them
+    fn get_step_sub_items(&self, instruction : usize) -> Option<usize> {
+        let sub_items = self.get_selected_pic().get_instructions().get(instruction)?.get_sub_item_count();
+        (self.playback_sub_items && sub_items > 1).then_some(sub_items)
+    }
+
+    // Moves on to the next instruction, or its first part when stepping through parts, returning false at the end
+    fn step_forward(&mut self) -> bool {
+        let (instruction, instruction_count) = (self.selected_instruction, self.get_selected_pic().get_instructions().len());
+        match (self.selected_sub_item, self.get_step_sub_items(instruction)) {
+            (Some(sub_item), Some(sub_items)) if sub_item + 2 < sub_items => self.select_instruction(instruction, Some(sub_item + 1)),
+            (Some(_), _) => self.select_instruction(instruction, None),
+            (None, _) if instruction + 1 < instruction_count => {
+                let sub_item = self.get_step_sub_items(instruction + 1).map(|_| 0);
+                self.select_instruction(instruction + 1, sub_item);
             },
-            CanvasView::PicBufferVectors => {
-                (Some(PixelBuffer::new(get_color(PIC_BUFFER_BASE_COLOR))), None, Some(ShapeBuffer::new()))
+            (None, _) => return false
+        }
+
+        self.scroll_to_selected_instruction = true;
+        true
+    }
+
+    fn step_back(&mut self) -> bool {
+        let instruction = self.selected_instruction;
+        match (self.selected_sub_item, self.get_step_sub_items(instruction)) {
+            (Some(sub_item), _) if sub_item > 0 => self.select_instruction(instruction, Some(sub_item - 1)),
+            (None, Some(sub_items)) => self.select_instruction(instruction, Some(sub_items - 2)),
+            _ if instruction > 0 => self.select_instruction(instruction - 1, None),
+            _ => return false
+        }
+
+        self.scroll_to_selected_instruction = true;
+        true
+    }
+
+    fn toggle_playback(&mut self) {
+        self.playing = !self.playing;
+        self.playback_time = 0.0;
+
+        // Playing from the end starts again from the top
+        if self.playing && self.selected_instruction + 1 >= self.get_selected_pic().get_instructions().len() && self.selected_sub_item.is_none() {
+            let sub_item = self.get_step_sub_items(0).map(|_| 0);
+            self.select_instruction(0, sub_item);
+            self.scroll_to_selected_instruction = true;
+        }
+    }
+
+    // Advances playback by however many steps are due after dt seconds
+    fn advance_playback(&mut self, dt : f32) {
+        self.playback_time += dt;
+
+        let step_time = 1.0 / self.playback_speed;
+        while self.playback_time >= step_time {
+            self.playback_time -= step_time;
+            if !self.step_forward() {
+                self.playing = false;
+                break;
+            }
+        }
+    }
+
+    fn handle_playback_keys(&mut self, ctx : &egui::Context) {
+        // Leave the keys alone while something like the export directory is being typed in, or a focused button would
+        // get Space as a click too
+        if ctx.wants_keyboard_input() || ctx.memory().focus().is_some() || self.game.pic_resources.is_empty() {
+            return;
+        }
+
+        let input = ctx.input();
+        let (space, right, left, home, end) = (input.key_pressed(Key::Space), input.key_pressed(Key::ArrowRight), input.key_pressed(Key::ArrowLeft), input.key_pressed(Key::Home), input.key_pressed(Key::End));
+        drop(input);
+
+        if space {
+            self.toggle_playback();
+        }
+        if right {
+            self.playing = false;
+            self.step_forward();
+        }
+        if left {
+            self.playing = false;
+            self.step_back();
+        }
+        if home {
+            self.select_instruction(0, None);
+            self.scroll_to_selected_instruction = true;
+        }
+        if end {
+            self.select_last_instruction();
+            self.scroll_to_selected_instruction = true;
+        }
+    }
+
+    fn playback_controls_ui(&mut self, ui : &mut Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("⏮").on_hover_text("First instruction (Home)").clicked() {
+                self.select_instruction(0, None);
+                self.scroll_to_selected_instruction = true;
             }
+            if ui.button("⏪").on_hover_text("Step back (Left)").clicked() {
+                self.playing = false;
+                self.step_back();
+            }
+            if ui.button(if self.playing { "⏸" } else { "▶" }).on_hover_text("Play/pause (Space)").clicked() {
+                self.toggle_playback();
+            }
+            if ui.button("⏩").on_hover_text("Step forward (Right)").clicked() {
+                self.playing = false;
+                self.step_forward();
+            }
+            if ui.button("⏭").on_hover_text("Last instruction (End)").clicked() {
+                self.select_last_instruction();
+                self.scroll_to_selected_instruction = true;
+            }
+        });
+        ui.add(Slider::new(&mut self.playback_speed, MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED).logarithmic(true).text("steps/s"));
+        ui.checkbox(&mut self.playback_sub_items, "Step through lines and fill points");
+    }
+
+    // The render options with the selected instruction's parts, so what's drawn matches the selection
+    fn get_render_options(&self) -> RenderOptions {
+        RenderOptions {
+            last_instruction_sub_items : self.selected_sub_item.map(|sub_item| sub_item + 1),
+            ..self.render_options
+        }
+    }
+
+    fn update_render_snapshots(&mut self) {
+        // Snapshots make clicking through the instruction list cheap, but they're only good for the picture they were made from
+        self.render_snapshots = self.game.pic_resources.get(self.selected_pic)
+            .and_then(|pic| RenderSnapshots::new(pic.get_instructions(), DEFAULT_SNAPSHOT_INTERVAL).ok());
+    }
+
+    // The raw resource data as other AGI tools export it, named for its resource number
+    fn export_resource(&self) -> Result<PathBuf, AgiError> {
+        let resource_index = self.game.all_resources[self.selected_pic].get_resource_index();
+        let data = self.get_selected_pic().to_bytes()?;
+
+        self.write_export(format!("pic.{:03}", resource_index), &data)
+    }
+
+    // The instructions drawn so far, up to and including the selected one, which an empty picture doesn't have
+    fn get_drawn_instructions(&self) -> Result<&[DerivedPicRenderInstruction], AgiError> {
+        let instructions = self.get_selected_pic().get_instructions();
+        instructions.get(..=self.selected_instruction)
+            .ok_or_else(|| AgiError::Export(format!("PIC {} has no instruction {}, it only has {}", self.selected_pic, self.selected_instruction, instructions.len())))
+    }
+
+    fn export_svg(&self) -> Result<PathBuf, AgiError> {
+        let options = SvgOptions { palette : self.palette, ..Default::default() };
+        let svg = instructions_to_svg(self.get_drawn_instructions()?, &options)?;
+
+        self.write_export(format!("pic_{:03}.svg", self.selected_pic), svg.as_bytes())
+    }
+
+    fn export_image(&self, buffer_type : PictureBufferType) -> Result<PathBuf, AgiError> {
+        let mut buffer = PixelBuffer::new(PIC_BUFFER_BASE_COLOR);
+        let (name, mut pic_buffer, mut pri_buffer) = match buffer_type {
+            PictureBufferType::Picture => ("pic", Some(&mut buffer), None),
+            PictureBufferType::Priority => ("pri", None, Some(&mut buffer))
         };
 
+        // Export what's on screen, so the render options apply here too
         render_to_buffers(
-            &pic.get_instructions()[0..=self.selected_instruction],
-            &self.render_options,
-            &mut pic_buffer.as_mut(),
-            &mut pri_buffer.as_mut(),
-            &mut pic_vectors.as_mut()).unwrap();
+            self.get_drawn_instructions()?,
+            &self.get_render_options(),
+            &mut pic_buffer,
+            &mut pri_buffer,
+            &mut None)?;
+
+        let options = ImageExportOptions { palette : self.palette, ..self.image_export_options };
+        let data = export_buffer(&buffer, &options)?;
+
+        self.write_export(format!("{}_{:03}.{}", name, self.selected_pic, options.format.extension()), &data)
+    }
+
+    fn export_animation(&self) -> Result<PathBuf, AgiError> {
+        let options = AnimationExportOptions {
+            image : ImageExportOptions { palette : self.palette, ..self.image_export_options },
+            ..self.animation_export_options
+        };
+        let data = export_animation(self.get_selected_pic().get_instructions(), PictureBufferType::Picture, &options)?;
+
+        self.write_export(format!("pic_{:03}_drawing.{}", self.selected_pic, options.format.extension()), &data)
+    }
+
+    fn write_export(&self, file_name : String, data : &[u8]) -> Result<PathBuf, AgiError> {
+        let export_dir = Path::new(&self.export_dir);
+        fs::create_dir_all(export_dir)?;
+        let path = export_dir.join(file_name);
+        fs::write(&path, data)?;
+
+        Ok(path)
+    }
+
+    fn get_palette_pixels(&self, buffer : &PixelBuffer) -> ColorImage {
+        let upscaled = self.upscaler.upscale(buffer.get_pixel_indexes(), VIEWPORT_WIDTH, VIEWPORT_HEIGHT, &self.palette);
+        ColorImage {
+            size: [upscaled.width, upscaled.height],
+            pixels: upscaled.to_colors(&self.palette)
+        }
+    }
+
+    fn generate_view(&self, pic : &PicResource, view : Rect, canvas_view_type : &CanvasView, line_width : f32, painter : &Painter) -> Result<GeneratedView, AgiError> {
+        // The actual image pixels, both buffers are always drawn so any pixel can be inspected whichever one is on show
+        let (mut pic_buffer, mut pri_buffer) = (Some(PixelBuffer::new(PIC_BUFFER_BASE_COLOR)), Some(PixelBuffer::new(PRI_BUFFER_BASE_COLOR)));
+        let mut pic_vectors = match canvas_view_type {
+            CanvasView::PicBufferVectors => Some(ShapeBuffer::new()),
+            _ => None
+        };
+
+        // Playback and stepping come through here too, and only replay the instructions since the closest snapshot
+        let render_options = self.get_render_options();
+        match &self.render_snapshots {
+            Some(snapshots) if snapshots.get_instruction_count() == pic.get_instructions().len() => {
+                snapshots.render_after_instruction(
+                    pic.get_instructions(),
+                    self.selected_instruction,
+                    &render_options,
+                    &mut pic_buffer.as_mut(),
+                    &mut pri_buffer.as_mut(),
+                    &mut pic_vectors.as_mut())?;
+            },
+            _ => {
+                render_to_buffers(
+                    pic.get_instructions().get(..=self.selected_instruction)
+                        .ok_or_else(|| AgiError::Render(format!("There is no instruction {} to draw up to", self.selected_instruction)))?,
+                    &render_options,
+                    &mut pic_buffer.as_mut(),
+                    &mut pri_buffer.as_mut(),
+                    &mut pic_vectors.as_mut())?;
+            }
+        }
     
         
-        let mut pixels : Option<Vec<Color32>> = None;
+        let mut pixels : Option<ColorImage> = None;
         let mut vectors : Option<Vec<Shape>> = None; 
 
+        let buffers = InspectedBuffers { pic_buffer : pic_buffer.unwrap(), pri_buffer : pri_buffer.unwrap() };
+
         match canvas_view_type {
-            CanvasView::PicBufferPixels => pixels = Some(pic_buffer.unwrap().get_pixels_vec()),
-            CanvasView::PriBufferPixels => pixels = Some(pri_buffer.unwrap().get_pixels_vec()),
+            CanvasView::PicBufferPixels => pixels = Some(self.get_palette_pixels(&buffers.pic_buffer)),
+            CanvasView::PriBufferPixels => pixels = Some(self.get_palette_pixels(&buffers.pri_buffer)),
             CanvasView::PicBufferVectors => {
                 if self.show_pixel_underlay {
-                    pixels = Some(pic_buffer.unwrap().get_pixels_vec());
+                    pixels = Some(self.get_palette_pixels(&buffers.pic_buffer));
                 }
-                vectors = Some(Self::draw_vectors(&view, line_width, &pic_vectors.unwrap(), painter));
+                vectors = Some(Self::draw_vectors(&view, line_width, &pic_vectors.unwrap(), &self.palette, painter));
             }
         }
-        (pixels, vectors)
+        Ok((pixels, vectors, buffers))
     }
 
-    fn draw_vectors(view : &Rect, line_width : f32, vectors : &ShapeBuffer, _painter : &Painter) -> Vec<Shape> {
+    // The AGI pixel under a point on the canvas, if it's over the picture
+    fn get_canvas_pixel(view : &Rect, pos : Pos2) -> Option<(usize, usize)> {
+        if !view.contains(pos) {
+            return None;
+        }
+
+        let (x_step, y_step) = Self::get_xy_step(view);
+        let x = ((pos.x - view.min.x) / x_step).floor() as usize;
+        let y = ((pos.y - view.min.y) / y_step).floor() as usize;
+
+        (x < VIEWPORT_WIDTH && y < VIEWPORT_HEIGHT).then_some((x, y))
+    }
+
+    // The instruction that last drew a pixel in the buffer on show
+    fn get_inspected_instruction(&self, x : usize, y : usize) -> Option<InstructionIndex> {
+        let buffers = self.inspected_buffers.as_ref()?;
+        let buffer = match self.selected_canvas_view {
+            CanvasView::PriBufferPixels => &buffers.pri_buffer,
+            _ => &buffers.pic_buffer
+        };
+        buffer.get_pixel_instruction(x, y).ok().flatten()
+    }
+
+    // Which of an instruction's lines or fill points a sub index is, when there's more than one
+    fn describe_sub_item(inst : &DerivedPicRenderInstruction, sub_index : usize) -> Option<String> {
+        match inst {
+            DerivedPicRenderInstruction::DrawLines(_, points) if points.len() > 2 && sub_index + 1 < points.len() => {
+                let (start, end) = (points[sub_index], points[sub_index + 1]);
+                Some(format!("line {} of {} ({},{})-({},{})", sub_index + 1, points.len() - 1, start.x, start.y, end.x, end.y))
+            },
+            DerivedPicRenderInstruction::Fill(_, points) if points.len() > 1 && sub_index < points.len() => {
+                Some(format!("fill {} of {} at ({},{})", sub_index + 1, points.len(), points[sub_index].x, points[sub_index].y))
+            },
+            _ => None
+        }
+    }
+
+    fn describe_pixel_instruction(&self, instruction : Option<InstructionIndex>) -> String {
+        let Some(instruction) = instruction else {
+            return String::from("Not drawn by any instruction");
+        };
+
+        let (index, sub_index) = (instruction.get_base_index(), instruction.get_sub_index());
+        let Some(inst) = self.get_selected_pic().get_instructions().get(index) else {
+            return format!("Instruction {}", index);
+        };
+
+        match Self::describe_sub_item(inst, sub_index) {
+            Some(part) => format!("{}. {}, {}", index, inst, part),
+            None => format!("{}. {}", index, inst)
+        }
+    }
+
+    // Marks the pixels drawn by the selected line or fill point, so one part of a big instruction can be picked out
+    fn get_highlight_pixels(&self) -> Option<ColorImage> {
+        let sub_index = self.selected_sub_item?;
+        let buffers = self.inspected_buffers.as_ref()?;
+        let buffer = match self.selected_canvas_view {
+            CanvasView::PriBufferPixels => &buffers.pri_buffer,
+            _ => &buffers.pic_buffer
+        };
+
+        let selected = InstructionIndex::new_sub(self.selected_instruction, sub_index);
+        let highlight = Color32::from_rgba_unmultiplied(0xFF, 0x00, 0xFF, 0xA0);
+        let pixels = (0..VIEWPORT_PIXELS)
+            .map(|i| match buffer.get_pixel_instruction(i % VIEWPORT_WIDTH, i / VIEWPORT_WIDTH) {
+                Ok(Some(index)) if index == selected => highlight,
+                _ => Color32::TRANSPARENT
+            })
+            .collect();
+
+        Some(ColorImage { size : [VIEWPORT_WIDTH, VIEWPORT_HEIGHT], pixels })
+    }
+
+    fn pixel_info_ui(&self, ui : &mut Ui, x : usize, y : usize) {
+        let Some(buffers) = &self.inspected_buffers else {
+            return;
+        };
+
+        ui.strong(format!("Pixel ({}, {})", x, y));
+
+        let color = buffers.pic_buffer.get_pixel_index(x, y).unwrap_or_default();
+        let priority = buffers.pri_buffer.get_pixel_index(x, y).unwrap_or_default();
+        ui.horizontal(|ui| {
+            let (swatch, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+            ui.painter().rect_filled(swatch, Rounding::none(), self.palette.get_color(color));
+            ui.label(format!("Colour {} ({})", color, get_color_str(color)));
+        });
+        ui.label(format!("Priority {}", priority));
+
+        ui.separator();
+        ui.label(format!("Picture: {}", self.describe_pixel_instruction(buffers.pic_buffer.get_pixel_instruction(x, y).ok().flatten())));
+        ui.label(format!("Priority: {}", self.describe_pixel_instruction(buffers.pri_buffer.get_pixel_instruction(x, y).ok().flatten())));
+    }
+
+    fn draw_vectors(view : &Rect, line_width : f32, vectors : &ShapeBuffer, palette : &Palette, _painter : &Painter) -> Vec<Shape> {
         let (x_step, y_step) = Self::get_xy_step(view);
 
         let mut shape_buffer = vec![];
 
         let (px_offset_x, px_offset_y) = (x_step / 2f32, y_step / 2f32);
 
+        // Fills go underneath all the lines, since the fill polygons run up to the middle of the lines that bound them
+        for fill in vectors.get_fills() {
+            let mut mesh = Mesh::default();
+            for v in fill.vertices.iter() {
+                mesh.colored_vertex(pos2((v.x * x_step) + view.min.x + px_offset_x, (v.y * y_step) + view.min.y + px_offset_y), palette.get_color(fill.agi_color));
+            }
+            mesh.indices = fill.triangles.iter().map(|i| *i as u32).collect();
+
+            shape_buffer.push(Shape::mesh(mesh));
+        }
+
         // Add lines here
         for path in vectors.get_paths() {
             if path.points.len() == 1 {
                 let p = path.points[0];
-                shape_buffer.push(Shape::circle_filled(pos2((p.x * x_step) + view.min.x + px_offset_x, (p.y * y_step) + view.min.y + px_offset_y), line_width / 2.0, path.color));
+                shape_buffer.push(Shape::circle_filled(pos2((p.x * x_step) + view.min.x + px_offset_x, (p.y * y_step) + view.min.y + px_offset_y), line_width / 2.0, palette.get_color(path.agi_color)));
             } else {
                 let translated_lines = path.points.iter()
                 .map(|p| {
@@ -118,7 +558,7 @@ impl AgiViewerApp {
                 })
                 .collect::<Vec<Pos2>>();
 
-                let line = Shape::line(translated_lines, Stroke::new(line_width, path.color));
+                let line = Shape::line(translated_lines, Stroke::new(line_width, palette.get_color(path.agi_color)));
 
                 shape_buffer.push(line);
             }
@@ -130,27 +570,142 @@ impl AgiViewerApp {
     fn get_xy_step(view : &Rect) -> (f32, f32) {
         (view.width() / VIEWPORT_WIDTH as f32, view.height() / VIEWPORT_HEIGHT as f32)
     }
+
+    // Where the picture goes on the canvas, which can be partly or entirely outside it when zoomed in
+    fn get_picture_rect(&self, canvas : Rect) -> Rect {
+        let picture_size = vec2(VIEWPORT_WIDTH as f32, VIEWPORT_HEIGHT as f32);
+        match self.canvas_fit {
+            Some(CanvasFit::Stretch) => canvas,
+            Some(CanvasFit::AspectCorrect) => {
+                let scale = (canvas.width() / (picture_size.x * 2.0)).min(canvas.height() / picture_size.y);
+                Rect::from_center_size(canvas.center(), vec2(picture_size.x * 2.0, picture_size.y) * scale)
+            },
+            None => Rect::from_min_size(canvas.min + self.canvas_offset, picture_size * self.canvas_scale)
+        }
+    }
+
+    // Shows the picture at its actual size, one screen pixel per AGI pixel, in the middle of the canvas
+    fn set_canvas_actual_size(&mut self, canvas : Rect, pixels_per_point : f32) {
+        self.canvas_fit = None;
+        self.canvas_scale = Vec2::splat(1.0 / pixels_per_point);
+        self.canvas_offset = (canvas.size() - vec2(VIEWPORT_WIDTH as f32, VIEWPORT_HEIGHT as f32) * self.canvas_scale) / 2.0;
+    }
+
+    // Switches from a fit to the equivalent scale and offset, so it can be zoomed or panned from where it is
+    fn unfit_canvas(&mut self, canvas : Rect) {
+        if self.canvas_fit.is_some() {
+            let picture = self.get_picture_rect(canvas);
+            self.canvas_fit = None;
+            self.canvas_scale = picture.size() / vec2(VIEWPORT_WIDTH as f32, VIEWPORT_HEIGHT as f32);
+            self.canvas_offset = picture.min - canvas.min;
+        }
+    }
+
+    // Zooms by factor, keeping the point under the cursor where it is
+    fn zoom_canvas(&mut self, canvas : Rect, cursor : Pos2, factor : f32) {
+        self.unfit_canvas(canvas);
+        let picture = self.get_picture_rect(canvas);
+
+        let max_factor = MAX_CANVAS_SCALE / self.canvas_scale.max_elem();
+        let min_factor = MIN_CANVAS_SCALE / self.canvas_scale.min_elem();
+        let factor = factor.max(min_factor).min(max_factor);
+
+        self.canvas_scale *= factor;
+        self.canvas_offset = (cursor - canvas.min) - (cursor - picture.min) * factor;
+    }
+
+    fn pan_canvas(&mut self, canvas : Rect, delta : Vec2) {
+        self.unfit_canvas(canvas);
+        self.canvas_offset += delta;
+    }
+
+    fn draw_pixel_grid(view : &Rect, canvas : &Rect, painter : &Painter) {
+        let (x_step, y_step) = Self::get_xy_step(view);
+        let visible = view.intersect(*canvas);
+        if x_step < PIXEL_GRID_MIN_SCALE || y_step < PIXEL_GRID_MIN_SCALE || !visible.is_positive() {
+            return;
+        }
+
+        let stroke = Stroke::new(1.0, Color32::from_black_alpha(96));
+
+        // Only the lines that can be seen, at high zoom most of the picture is off the canvas
+        let first_x = ((visible.min.x - view.min.x) / x_step).floor() as usize;
+        let last_x = (((visible.max.x - view.min.x) / x_step).ceil() as usize).min(VIEWPORT_WIDTH);
+        for x in first_x..=last_x {
+            let screen_x = view.min.x + x as f32 * x_step;
+            painter.line_segment([pos2(screen_x, visible.min.y), pos2(screen_x, visible.max.y)], stroke);
+        }
+
+        let first_y = ((visible.min.y - view.min.y) / y_step).floor() as usize;
+        let last_y = (((visible.max.y - view.min.y) / y_step).ceil() as usize).min(VIEWPORT_HEIGHT);
+        for y in first_y..=last_y {
+            let screen_y = view.min.y + y as f32 * y_step;
+            painter.line_segment([pos2(visible.min.x, screen_y), pos2(visible.max.x, screen_y)], stroke);
+        }
+    }
 }
 
 impl eframe::App for AgiViewerApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if self.thumbnail_texture_handles.is_empty() {
-            // Load the thumbnail textures
-            self.game.pic_resources.iter().enumerate().for_each(|(i, r)| {
-                let mut pic_buffer = PixelBuffer::new(Color32::WHITE);
-                render_to_buffers(r.get_instructions(), &RenderOptions::default(), &mut Some(&mut pic_buffer), &mut None, &mut None).unwrap();
-
-                let image_data = ColorImage {
-                    size: [VIEWPORT_WIDTH, VIEWPORT_HEIGHT],
-                    pixels: pic_buffer.get_pixels_vec()
-                };
-                self.thumbnail_texture_handles.push(ctx.load_texture(format!("PIC {}", i), image_data, Default::default()));
-            });
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.handle_dropped_files(ctx);
+        if self.window_title_changed {
+            frame.set_window_title(&self.get_window_title());
+            self.window_title_changed = false;
+        }
+
+        if self.thumbnail_texture_handles.is_empty() && !self.game.pic_resources.is_empty() {
+            // Start with blank thumbnails, and render the real ones in the background so the window comes up straight away
+            for i in 0..self.game.pic_resources.len() {
+                let blank = ColorImage::new([VIEWPORT_WIDTH, VIEWPORT_HEIGHT], Color32::WHITE);
+                self.thumbnail_texture_handles.push(ctx.load_texture(format!("PIC {}", i), blank, Default::default()));
+            }
+
+            self.thumbnail_renders = Some(render_batch(self.game.pic_resources.clone(), None));
+        }
+
+        if let Some(thumbnail_renders) = &self.thumbnail_renders {
+            // Pick up whatever thumbnails have finished since the last frame, until the workers are all done and the
+            // channel closes, which it also does if one of them fails without sending anything
+            let mut finished = false;
+            loop {
+                match thumbnail_renders.try_recv() {
+                    Ok(rendered) => {
+                        // Edited pictures already have a newer thumbnail than the batch, which rendered them as loaded
+                        if self.edit_histories.contains_key(&rendered.pic_index) || self.unsaved_pics.contains(&rendered.pic_index) {
+                            continue;
+                        }
+
+                        match rendered.result {
+                            Ok(rendered_pic) => {
+                                let image_data = ColorImage {
+                                    size: [VIEWPORT_WIDTH, VIEWPORT_HEIGHT],
+                                    pixels: rendered_pic.pic_buffer.get_pixels_vec()
+                                };
+                                self.thumbnail_texture_handles[rendered.pic_index].set(image_data, Default::default());
+                            },
+                            Err(err) => println!("Error rendering thumbnail for PIC {}: {:?}", rendered.pic_index, err)
+                        }
+                    },
+                    Err(TryRecvError::Empty) => {
+                        // Keep checking in until they're all done
+                        ctx.request_repaint();
+                        break;
+                    },
+                    Err(TryRecvError::Disconnected) => {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+
+            if finished {
+                self.thumbnail_renders = None;
+            }
         }
 
         if self.main_viewport_texture.is_none() {
             // Blank image
-            let pixels = Box::new([Color32::WHITE ; VIEWPORT_PIXELS]).to_vec();
+            let pixels = vec![Color32::WHITE ; VIEWPORT_PIXELS];
             let blank = ColorImage {
                 size: [VIEWPORT_WIDTH, VIEWPORT_HEIGHT],
                 pixels
@@ -158,26 +713,48 @@ impl eframe::App for AgiViewerApp {
             self.main_viewport_texture = Some(ctx.load_texture("MAIN_BUFFER", blank, Default::default()));
         }
 
+        egui::TopBottomPanel::top("game").show(ctx, |ui| self.game_menu_ui(ui));
+        self.game_browser_ui(ctx);
+        self.unsaved_changes_ui(ctx);
+
+        if self.game.pic_resources.is_empty() {
+            egui::CentralPanel::default().show(ctx, |ui| self.start_screen_ui(ui));
+            return;
+        }
+
+        self.handle_playback_keys(ctx);
+        self.handle_edit_keys(ctx);
+        if self.playing {
+            let dt = ctx.input().stable_dt;
+            self.advance_playback(dt);
+            ctx.request_repaint();
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered_justified(|ui| {
                 // Thumbnails
                 ScrollArea::horizontal().auto_shrink([true; 2]).show(ui, |ui| {
                     ui.set_max_height(150.);
                     ui.horizontal_centered(|ui| {
-                        for (i, _resource) in self.game.pic_resources.iter().enumerate() {
+                        for i in 0..self.game.pic_resources.len() {
 
                             ui.vertical(|ui| {
                                 ui.style_mut().wrap = Some(false);
-                                ui.label(format!("PIC {}", i));
+                                if self.unsaved_pics.contains(&i) {
+                                    ui.label(RichText::new(format!("PIC {} *", i)).strong()).on_hover_text("Edited since it was last saved");
+                                } else {
+                                    ui.label(format!("PIC {}", i));
+                                }
                                 let image_button = ImageButton::new(self.thumbnail_texture_handles[i].id(), vec2(VIEWPORT_WIDTH as f32, VIEWPORT_HEIGHT as f32 / 2.0))
                                     .selected(i == self.selected_pic);
 
                                 if ui.add(image_button).clicked() {
                                     self.selected_pic = i;
-                                    self.selected_instruction = self.game.pic_resources[i].get_instructions().len() - 1;
-                                    
-                                    // Hack to invalidate and redraw everything
-                                    self.canvas_view_rect = Rect::NOTHING;
+                                    self.playing = false;
+                                    self.drawing_instruction = None;
+                                    self.dragged_point = None;
+                                    self.select_last_instruction();
+                                    self.update_render_snapshots();
                                 }
                                 ui.set_max_width(VIEWPORT_WIDTH as f32 + 5.0);
                             });
@@ -191,29 +768,50 @@ impl eframe::App for AgiViewerApp {
                     ui.vertical(|ui| {
                         ui.set_max_width(250.);
 
-                        ui.label(format!("Instruction List ({}/{})", self.selected_instruction, self.get_selected_pic().get_instructions().len()));                        
+                        let part = match self.selected_sub_item {
+                            Some(sub_item) => format!(", part {}/{}", sub_item + 1, self.get_selected_pic().get_instructions()[self.selected_instruction].get_sub_item_count()),
+                            None => String::new()
+                        };
+                        ui.label(format!("Instruction List ({}/{}{})", self.selected_instruction, self.get_selected_pic().get_instructions().len(), part));
+                        ui.separator();
+                        self.playback_controls_ui(ui);
+                        ui.separator();
+                        self.editor_ui(ui);
                         ui.separator();
                         ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
                             for i in 0..(self.get_selected_pic().get_instructions().len()) {
                                 let inst_text = format!("{}. {}", i, self.get_selected_pic().get_instructions()[i]);
                                 ui.style_mut().wrap = Some(false);
                                 
-                                let button = ui.selectable_value(&mut self.selected_instruction, i, inst_text);
+                                let button = ui.selectable_label(i == self.selected_instruction && self.selected_sub_item.is_none(), inst_text);
                                 if button.clicked() {
-                                    // Invalidates the view for redraw
-                                    self.canvas_view_rect = Rect::NOTHING;
+                                    self.playing = false;
+                                    self.select_instruction(i, None);
+                                }
+
+                                if self.scroll_to_selected_instruction && i == self.selected_instruction {
+                                    button.scroll_to_me(Some(Align::Center));
+                                }
+
+                                // The selected instruction opens out into its lines or fill points, if it has more than one
+                                if i == self.selected_instruction {
+                                    let inst = &self.get_selected_pic().get_instructions()[i];
+                                    let sub_items : Vec<String> = (0..inst.get_sub_item_count()).filter_map(|sub_index| Self::describe_sub_item(inst, sub_index)).collect();
+                                    ui.indent("sub_items", |ui| {
+                                        for (sub_index, sub_item) in sub_items.into_iter().enumerate() {
+                                            if ui.selectable_label(self.selected_sub_item == Some(sub_index), sub_item).clicked() {
+                                                self.playing = false;
+                                                self.select_instruction(i, Some(sub_index));
+                                            }
+                                        }
+                                    });
                                 }
                             }
+                            self.scroll_to_selected_instruction = false;
                         });
                     });
                     
                     ui.vertical(|ui| {
-                        /*if response.clicked() {
-                            self.pointer_loc = response.interact_pointer_pos().unwrap();
-                            self.pointer_loc.x = ((self.pointer_loc.x - response.rect.min.x) / x_step).floor();
-                            self.pointer_loc.y = ((self.pointer_loc.y - response.rect.min.y) / y_step).floor();
-                        }*/
-
                         let available_space = ui.available_size_before_wrap();
 
                         // Canvas selector tab
@@ -231,6 +829,97 @@ impl eframe::App for AgiViewerApp {
 
                             // Render options button
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                ui.menu_button("Export ☰", |ui| {
+                                    ui.vertical(|ui| {
+                                        ui.set_width(200f32);
+                                        ui.label("Export Directory");
+                                        ui.text_edit_singleline(&mut self.export_dir);
+                                        ui.separator();
+
+                                        let mut export_result = None;
+
+                                        ui.strong("Image");
+                                        ComboBox::from_label("Format").selected_text(self.image_export_options.format.extension()).show_ui(ui, |ui| {
+                                            for format in ImageFormat::ALL {
+                                                ui.selectable_value(&mut self.image_export_options.format, format, format.extension());
+                                            }
+                                        });
+                                        ComboBox::from_label("Export Upscaler").selected_text(self.image_export_options.upscaler.name()).show_ui(ui, |ui| {
+                                            for upscaler in Upscaler::ALL {
+                                                ui.selectable_value(&mut self.image_export_options.upscaler, upscaler, upscaler.name());
+                                            }
+                                        });
+                                        ui.checkbox(&mut self.image_export_options.stretch, "Stretch to 2:1 (320x168)");
+                                        ui.add(Slider::new(&mut self.image_export_options.scale, 1..=8).text("Scale"));
+                                        if ui.button("Save Picture Buffer").clicked() {
+                                            export_result = Some(self.export_image(PictureBufferType::Picture));
+                                        }
+                                        if ui.button("Save Priority Buffer").clicked() {
+                                            export_result = Some(self.export_image(PictureBufferType::Priority));
+                                        }
+
+                                        ui.separator();
+                                        ui.strong("Animation");
+                                        ComboBox::from_label("Animation Format").selected_text(self.animation_export_options.format.name()).show_ui(ui, |ui| {
+                                            for format in AnimationFormat::ALL {
+                                                ui.selectable_value(&mut self.animation_export_options.format, format, format.name());
+                                            }
+                                        });
+                                        ui.add(Slider::new(&mut self.animation_export_options.instructions_per_frame, 1..=32).text("Instructions per frame"));
+                                        ui.add(Slider::new(&mut self.animation_export_options.frame_delay_ms, 10..=1000).text("Frame delay (ms)"));
+                                        if ui.button("Save Animation").clicked() {
+                                            export_result = Some(self.export_animation());
+                                        }
+
+                                        ui.separator();
+                                        ui.strong("Vectors");
+                                        if ui.button("Save SVG").clicked() {
+                                            export_result = Some(self.export_svg());
+                                        }
+
+                                        ui.separator();
+                                        ui.strong("Resource");
+                                        if ui.button("Save PIC resource (pic.NNN)").clicked() {
+                                            export_result = Some(self.export_resource());
+                                        }
+
+                                        if let Some(export_result) = export_result {
+                                            self.export_status = Some(match export_result {
+                                                Ok(path) => format!("Saved {}", path.display()),
+                                                Err(err) => format!("Export failed: {:?}", err)
+                                            });
+                                        }
+
+                                        if let Some(export_status) = &self.export_status {
+                                            ui.separator();
+                                            ui.label(export_status);
+                                        }
+                                    });
+                                });
+
+                                ui.menu_button("Compare ☰", |ui| self.compare_menu_ui(ui));
+
+                                ui.menu_button("Zoom ☰", |ui| {
+                                    ui.vertical(|ui| {
+                                        ui.set_width(200f32);
+                                        let canvas = self.canvas_rect;
+                                        if ui.selectable_label(self.canvas_fit == Some(CanvasFit::Stretch), "Fit to canvas").clicked() {
+                                            self.canvas_fit = Some(CanvasFit::Stretch);
+                                        }
+                                        if ui.selectable_label(self.canvas_fit == Some(CanvasFit::AspectCorrect), "Aspect correct (2:1 pixels)").clicked() {
+                                            self.canvas_fit = Some(CanvasFit::AspectCorrect);
+                                        }
+                                        if ui.button("Actual size (1:1)").clicked() {
+                                            self.set_canvas_actual_size(canvas, ui.ctx().pixels_per_point());
+                                        }
+                                        ui.separator();
+                                        ui.checkbox(&mut self.show_pixel_grid, "Show pixel grid when zoomed in");
+                                        let (x, y) = Self::get_xy_step(&self.get_picture_rect(canvas));
+                                        ui.label(format!("Zoom {:.0}% x {:.0}%", x * 100.0, y * 100.0));
+                                        ui.label("Scroll to zoom, drag to pan");
+                                    });
+                                });
+
                                 ui.menu_button("Render Options ☰", |ui| {
                                     
                                     ui.vertical(|ui| {
@@ -245,6 +934,22 @@ impl eframe::App for AgiViewerApp {
                                             self.canvas_view_rect = Rect::NOTHING;
                                         }
 
+                                        ComboBox::from_label("Palette").selected_text(self.palette.name).show_ui(ui, |ui| {
+                                            for palette in Palette::ALL {
+                                                if ui.selectable_value(&mut self.palette, palette, palette.name).clicked() {
+                                                    self.canvas_view_rect = Rect::NOTHING;
+                                                }
+                                            }
+                                        });
+
+                                        ComboBox::from_label("Upscaler").selected_text(self.upscaler.name()).show_ui(ui, |ui| {
+                                            for upscaler in Upscaler::ALL {
+                                                if ui.selectable_value(&mut self.upscaler, upscaler, upscaler.name()).clicked() {
+                                                    self.canvas_view_rect = Rect::NOTHING;
+                                                }
+                                            }
+                                        });
+
                                         ui.vertical(|ui| {
                                             ui.set_enabled(self.selected_canvas_view == CanvasView::PicBufferVectors);
 
@@ -271,24 +976,60 @@ impl eframe::App for AgiViewerApp {
                         let canvas_size = vec2(available_space.x, available_space.y - label_height - 5.0);
 
                         Frame::canvas(ui.style()).rounding(Rounding::none()).inner_margin(Margin::default()).show(ui, |ui| {
-                            let (response, painter) = ui.allocate_painter(canvas_size, Sense::click());
-                            
-                            let view = response.rect;
+                            let (response, canvas_painter) = ui.allocate_painter(canvas_size, Sense::click_and_drag());
+                            // Comparing with another game splits the canvas, with the other picture on the right
+                            let (canvas, compare_canvas) = self.split_canvas(response.rect);
+                            let painter = canvas_painter.with_clip_rect(canvas);
+                            self.canvas_rect = canvas;
+
+                            // Zoom and pan, scrolling zooms around the pointer and dragging moves the picture
+                            if let Some(pointer) = response.hover_pos() {
+                                // Over the other picture, zoom around the same spot in this one
+                                let pointer = match compare_canvas {
+                                    Some(compare_canvas) if compare_canvas.contains(pointer) => pointer - (compare_canvas.min - canvas.min),
+                                    _ => pointer
+                                };
+                                let (scroll, zoom) = (ui.input().scroll_delta.y, ui.input().zoom_delta());
+                                let factor = zoom * (scroll / 200.0).exp();
+                                if factor != 1.0 {
+                                    self.zoom_canvas(canvas, pointer, factor);
+                                }
+                            }
+                            // Dragging a point with the move tool edits the picture, anything else pans
+                            let editing = self.handle_edit_drag(&response, &self.get_picture_rect(canvas));
+                            if response.dragged() && !editing {
+                                self.pan_canvas(canvas, response.drag_delta());
+                            }
 
-                            if response.rect != self.canvas_view_rect || self.new_line_width != self.line_width {
-                                self.canvas_view_rect = response.rect;
+                            let view = self.get_picture_rect(canvas);
+
+                            if view != self.canvas_view_rect && view.size() == self.canvas_view_rect.size() && self.new_line_width == self.line_width {
+                                // Just panned, so the picture only needs to move
+                                let delta = view.min - self.canvas_view_rect.min;
+                                self.canvas_view_shapes.iter_mut().for_each(|shape| shape.translate(delta));
+                                self.comparison.shapes.iter_mut().for_each(|shape| shape.translate(delta));
+                                self.canvas_view_rect = view;
+                            }
+
+                            if view != self.canvas_view_rect || self.new_line_width != self.line_width {
+                                self.canvas_view_rect = view;
                                 self.line_width = self.new_line_width;
 
-                                let (pixels, vectors) = self.generate_view(self.get_selected_pic(), view, &self.selected_canvas_view, self.line_width, &painter);
+                                let (pixels, vectors, buffers) = match self.generate_view(self.get_selected_pic(), view, &self.selected_canvas_view, self.line_width, &painter) {
+                                    Ok((pixels, vectors, buffers)) => {
+                                        self.render_error = None;
+                                        (pixels, vectors, Some(buffers))
+                                    },
+                                    Err(err) => {
+                                        self.render_error = Some(format!("Render failed: {:?}", err));
+                                        (None, None, None)
+                                    }
+                                };
+                                self.inspected_buffers = buffers;
 
                                 self.canvas_view_shapes.clear();
 
-                                if let Some(pixels) = pixels {
-                                    let image_data = ColorImage {
-                                        size: [VIEWPORT_WIDTH, VIEWPORT_HEIGHT],
-                                        pixels
-                                    };
-
+                                if let Some(image_data) = pixels {
                                     self.main_viewport_texture.as_mut().unwrap().set(image_data, TextureOptions::NEAREST);
                                     let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
 
@@ -307,21 +1048,77 @@ impl eframe::App for AgiViewerApp {
                                         self.canvas_view_shapes.push(Shape::rect_filled(view, Rounding::none(), Color32::WHITE));
                                     } else {
                                         // We are drawing the pixel underlay, so create an outline for our vectors
-                                        let outlines : Vec<Shape> = vectors.iter().map(|v| {
+                                        let outlines : Vec<Shape> = vectors.iter().filter_map(|v| {
                                             match v {
                                                 Shape::Path(s) => Some(Shape::line(s.points.clone(), Stroke::new(self.line_width+1.5, Color32::WHITE))),
                                                 _ => None
                                             }
                                         })
-                                        .flatten()
                                         .collect();
                                         self.canvas_view_shapes.extend(outlines);
                                     }
                                     self.canvas_view_shapes.extend(vectors);
                                 }
+
+                                // Isolating the instruction already shows just the selected part, so there's nothing to pick out
+                                if let Some(highlight) = self.get_highlight_pixels().filter(|_| !self.render_options.render_only_selected_instruction) {
+                                    let texture = self.highlight_texture.get_or_insert_with(|| ui.ctx().load_texture("HIGHLIGHT", highlight.clone(), TextureOptions::NEAREST));
+                                    texture.set(highlight, TextureOptions::NEAREST);
+                                    let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+                                    self.canvas_view_shapes.push(Shape::image(texture.id(), view, uv, Color32::WHITE));
+                                }
+
+                                if let Some(compare_canvas) = compare_canvas {
+                                    self.generate_comparison(ui.ctx(), view, view.translate(compare_canvas.min - canvas.min), &painter);
+                                }
                             }
 
                             painter.extend(self.canvas_view_shapes.clone());
+
+                            if let Some(render_error) = &self.render_error {
+                                painter.text(canvas.center(), Align2::CENTER_CENTER, render_error, FontId::proportional(16.0), Color32::RED);
+                            }
+
+                            if self.show_pixel_grid {
+                                Self::draw_pixel_grid(&view, &canvas, &painter);
+                            }
+
+                            if let Some(compare_canvas) = compare_canvas {
+                                let compare_painter = canvas_painter.with_clip_rect(compare_canvas);
+                                self.draw_comparison(compare_canvas, &compare_painter);
+                                if self.show_pixel_grid {
+                                    let compare_view = view.translate(compare_canvas.min - canvas.min);
+                                    Self::draw_pixel_grid(&compare_view, &compare_canvas, &compare_painter);
+                                }
+                            }
+
+                            // Pixel inspector, hovering shows what's under the pointer and clicking jumps to the instruction that drew it
+                            if let Some((x, y)) = response.hover_pos().filter(|pos| canvas.contains(*pos)).and_then(|pos| Self::get_canvas_pixel(&view, pos)) {
+                                let (x_step, y_step) = Self::get_xy_step(&view);
+                                let pixel_rect = Rect::from_min_size(pos2(view.min.x + x as f32 * x_step, view.min.y + y as f32 * y_step), vec2(x_step, y_step));
+                                painter.rect_stroke(pixel_rect.expand(1.0), Rounding::none(), Stroke::new(1.0, Color32::from_rgb(0xFF, 0x00, 0xFF)));
+
+                                // The instruction list has already been drawn for this frame, so show the click's effects in the next
+                                if response.clicked() || response.secondary_clicked() {
+                                    ui.ctx().request_repaint();
+                                }
+
+                                if self.edit_tool != EditTool::Inspect {
+                                    self.handle_edit_click(&response, x, y);
+                                } else {
+                                    if response.clicked() {
+                                        if let Some(instruction) = self.get_inspected_instruction(x, y) {
+                                            self.playing = false;
+                                            self.select_instruction(instruction.get_base_index(), None);
+                                            self.scroll_to_selected_instruction = true;
+                                        }
+                                    }
+
+                                    response.on_hover_ui_at_pointer(|ui| self.pixel_info_ui(ui, x, y));
+                                }
+                            }
+
+                            self.draw_edit_points(&view, &painter);
                         });
                     });
                 });
@@ -332,16 +1129,26 @@ impl eframe::App for AgiViewerApp {
 
 
 fn main() -> Result<(), AgiError> {
+    let cli = Cli::parse();
 
-    let args : Vec<_> = env::args().collect();
-
-    if args.len() < 2 {
-        println!("Please provide a path to an existing AGI game on your machine, e.g.:");
-        println!("   agi-pic-viewer \"C:\\Program Files (x86)\\GOG Galaxy\\Games\\Kings Quest 2\\\"");
-        return Ok(());
+    match (cli.command, cli.game_dir) {
+        (Some(Command::View { game_dir }), _) | (None, Some(game_dir)) => run_viewer(Some(&game_dir)),
+        (Some(command), _) => {
+            if !cli::run(command)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        },
+        (None, None) => run_viewer(None)
     }
+}
 
-    let game = Game::new_from_dir(Path::new(&args[1]))?;
+// Without a game directory the viewer starts empty, and one can be opened from inside it
+fn run_viewer(game_dir : Option<&Path>) -> Result<(), AgiError> {
+    let game = match game_dir {
+        Some(game_dir) => Game::new_from_dir(game_dir)?,
+        None => open_game::no_game()
+    };
 
     let width = 1400.;
     let height = 800.;
@@ -351,10 +1158,15 @@ fn main() -> Result<(), AgiError> {
         ..Default::default()
     };
     
+    let mut app = AgiViewerApp::new(game, open_game::load_recent_games());
+    if let Some(game_dir) = game_dir {
+        app.add_recent_game(game_dir);
+    }
+
     eframe::run_native(
-        format!("AGI Pic Viewer - {}", game.dir_name).as_str(),
+        app.get_window_title().as_str(),
         options,
-        Box::new(|_cc| Box::new(AgiViewerApp::new(game))),
+        Box::new(|_cc| Box::new(app)),
     );
 
     Ok(())
@@ -0,0 +1,338 @@
+use std::{env, fs, path::{Path, PathBuf}};
+use agi_types::{common::*, game::*};
+use eframe::egui;
+use egui::*;
+
+use crate::AgiViewerApp;
+
+const MAX_RECENT_GAMES : usize = 10;
+
+/*
+The recent games are kept as a JSON list of paths in the usual per-user config directory for the platform, e.g.
+~/.config/agi-pic-viewer/recent_games.json on Linux.  There's nothing else worth remembering between sessions yet, and
+if the file can't be read or written the viewer carries on without it.
+*/
+fn get_recent_games_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+
+    config_dir.map(|dir| dir.join("agi-pic-viewer").join("recent_games.json"))
+}
+
+fn read_recent_games(path : &Path) -> Vec<PathBuf> {
+    fs::read(path).ok()
+        .and_then(|data| serde_json::from_slice::<Vec<PathBuf>>(&data).ok())
+        .unwrap_or_default()
+}
+
+fn write_recent_games(path : &Path, recent_games : &[PathBuf]) -> Result<(), AgiError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(recent_games).map_err(|err| AgiError::Export(err.to_string()))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load_recent_games() -> Vec<PathBuf> {
+    get_recent_games_path().map(|path| read_recent_games(&path)).unwrap_or_default()
+}
+
+// Moves game_dir to the top of the list, dropping the oldest once it's full
+fn add_recent_game(recent_games : &mut Vec<PathBuf>, game_dir : &Path) {
+    recent_games.retain(|dir| dir != game_dir);
+    recent_games.insert(0, game_dir.to_path_buf());
+    recent_games.truncate(MAX_RECENT_GAMES);
+}
+
+fn is_game_dir(dir : &Path) -> bool {
+    dir.join("PICDIR").is_file()
+}
+
+// An empty game for when the viewer is started without one
+pub fn no_game() -> Game {
+    Game::new_from_data("", &[], &[])
+}
+
+// There's no native file dialog to hand, so the Open window browses directories itself
+pub struct GameBrowser {
+    pub dir : String,
+    // Add the game alongside the open one to compare with it, rather than switching to it
+    pub compare : bool,
+    subdirs : Vec<(String, bool)>,
+    listed_dir : Option<PathBuf>
+}
+
+impl GameBrowser {
+    pub fn new(dir : &Path) -> Self {
+        GameBrowser {
+            dir : dir.to_string_lossy().to_string(),
+            compare : false,
+            subdirs : vec![],
+            listed_dir : None
+        }
+    }
+
+    // The directories under the current one, and whether each holds a game, reread whenever the directory changes
+    fn list_subdirs(&mut self) {
+        let dir = PathBuf::from(&self.dir);
+        if self.listed_dir.as_ref() == Some(&dir) {
+            return;
+        }
+
+        self.subdirs = fs::read_dir(&dir).map(|entries| {
+            let mut subdirs : Vec<(String, bool)> = entries.filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .map(|path| (path.file_name().unwrap_or_default().to_string_lossy().to_string(), is_game_dir(&path)))
+                .collect();
+            subdirs.sort_by_key(|(name, _)| name.to_lowercase());
+            subdirs
+        }).unwrap_or_default();
+        self.listed_dir = Some(dir);
+    }
+}
+
+impl AgiViewerApp {
+    fn has_game(&self) -> bool {
+        !self.game.dir_name.is_empty()
+    }
+
+    // Opens the game in game_dir, asking first if there are edits that haven't been saved
+    pub fn open_game(&mut self, game_dir : &Path) {
+        if !self.unsaved_pics.is_empty() {
+            self.pending_open = Some(game_dir.to_path_buf());
+            return;
+        }
+
+        self.reopen_game(game_dir);
+    }
+
+    // Opens the game in game_dir regardless of any unsaved edits, which are lost if it opens
+    fn reopen_game(&mut self, game_dir : &Path) {
+        match Game::new_from_dir(game_dir) {
+            Ok(game) => {
+                self.add_recent_game(game_dir);
+                self.game_browser = None;
+                self.open_status = None;
+                self.load_game(game);
+            },
+            Err(err) => self.open_status = Some(format!("Couldn't open {}: {:?}", game_dir.display(), err))
+        }
+    }
+
+    pub fn add_recent_game(&mut self, game_dir : &Path) {
+        // Stored absolute so they still work from wherever the viewer is started next time
+        let game_dir = fs::canonicalize(game_dir).unwrap_or_else(|_| game_dir.to_path_buf());
+        add_recent_game(&mut self.recent_games, &game_dir);
+        if let Some(path) = get_recent_games_path() {
+            if let Err(err) = write_recent_games(&path, &self.recent_games) {
+                println!("Error saving the recent games to {}: {:?}", path.display(), err);
+            }
+        }
+    }
+
+    // Swaps in a new game, dropping everything that belonged to the old one
+    pub fn load_game(&mut self, game : Game) {
+        self.game = game;
+        self.selected_pic = 0;
+        self.thumbnail_texture_handles.clear();
+        self.thumbnail_renders = None;
+        self.inspected_buffers = None;
+        self.render_error = None;
+        self.edit_histories.clear();
+        self.unsaved_pics.clear();
+        self.save_status = None;
+        self.drawing_instruction = None;
+        self.dragged_point = None;
+        self.playing = false;
+        self.window_title_changed = true;
+
+        let instruction_count = self.game.pic_resources.first().map_or(0, |pic| pic.get_instructions().len());
+        self.select_instruction(instruction_count.saturating_sub(1), None);
+        self.update_render_snapshots();
+    }
+
+    pub fn get_window_title(&self) -> String {
+        if self.has_game() {
+            format!("AGI Pic Viewer - {}", self.game.dir_name)
+        } else {
+            String::from("AGI Pic Viewer")
+        }
+    }
+
+    // A game directory, or any file in one, dropped on the window opens it
+    pub fn handle_dropped_files(&mut self, ctx : &egui::Context) {
+        let dropped = ctx.input().raw.dropped_files.first().and_then(|file| file.path.clone());
+        if let Some(path) = dropped {
+            let game_dir = if path.is_dir() { path } else { path.parent().map(Path::to_path_buf).unwrap_or_default() };
+            self.open_game(&game_dir);
+        }
+    }
+
+    pub fn game_menu_ui(&mut self, ui : &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.menu_button("Game ☰", |ui| {
+                ui.set_width(300f32);
+                if ui.button("Open…").clicked() {
+                    let start_dir = if self.has_game() { Path::new(&self.game.dir_name).parent().map(Path::to_path_buf) } else { env::current_dir().ok() };
+                    self.game_browser = Some(GameBrowser::new(&start_dir.unwrap_or_default()));
+                    ui.close_menu();
+                }
+
+                ui.separator();
+                ui.strong("Recent");
+                if self.recent_games.is_empty() {
+                    ui.label("No recent games");
+                }
+                let mut opened = None;
+                for game_dir in &self.recent_games {
+                    if ui.button(game_dir.to_string_lossy()).clicked() {
+                        opened = Some(game_dir.clone());
+                        ui.close_menu();
+                    }
+                }
+                if let Some(game_dir) = opened {
+                    self.open_game(&game_dir);
+                }
+            });
+
+            ui.label(if self.has_game() { self.game.dir_name.as_str() } else { "No game open" });
+            if let Some(open_status) = &self.open_status {
+                ui.colored_label(ui.visuals().error_fg_color, open_status);
+            }
+        });
+    }
+
+    // What the window shows until a game is opened
+    pub fn start_screen_ui(&mut self, ui : &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            if self.has_game() {
+                ui.heading("There are no pictures in this game");
+            } else {
+                ui.heading("Open a game to view its pictures");
+            }
+            ui.label("Use the Game menu, or drop a game directory onto the window");
+        });
+    }
+
+    pub fn game_browser_ui(&mut self, ctx : &egui::Context) {
+        let Some(browser) = &mut self.game_browser else {
+            return;
+        };
+
+        let mut open = true;
+        let mut opened = None;
+        let title = if browser.compare { "Add Game to Compare" } else { "Open Game" };
+        Window::new(title).open(&mut open).collapsible(false).default_width(400f32).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⬆").on_hover_text("Up a directory").clicked() {
+                    if let Some(parent) = Path::new(&browser.dir).parent() {
+                        browser.dir = parent.to_string_lossy().to_string();
+                    }
+                }
+                ui.add(TextEdit::singleline(&mut browser.dir).desired_width(f32::INFINITY));
+            });
+            ui.separator();
+
+            browser.list_subdirs();
+            ScrollArea::vertical().max_height(300f32).auto_shrink([false, true]).show(ui, |ui| {
+                if browser.subdirs.is_empty() {
+                    ui.label("No directories here");
+                }
+                for (name, is_game) in &browser.subdirs {
+                    let label = if *is_game { RichText::new(format!("🎮 {}", name)).strong() } else { RichText::new(format!("🗀 {}", name)) };
+                    let response = ui.selectable_label(false, label);
+                    if response.double_clicked() && *is_game {
+                        opened = Some(Path::new(&browser.dir).join(name));
+                    } else if response.clicked() {
+                        browser.dir = Path::new(&browser.dir).join(name).to_string_lossy().to_string();
+                    }
+                }
+            });
+            ui.separator();
+
+            let dir = PathBuf::from(&browser.dir);
+            ui.horizontal(|ui| {
+                let is_game = is_game_dir(&dir);
+                if ui.add_enabled(is_game, Button::new("Open")).clicked() {
+                    opened = Some(dir.clone());
+                }
+                ui.label(if is_game { "This directory has a game in it" } else { "Pick a directory with a PICDIR file in it" });
+            });
+        });
+
+        if !open {
+            self.game_browser = None;
+        }
+        if let Some(game_dir) = opened {
+            if self.game_browser.as_ref().is_some_and(|browser| browser.compare) {
+                self.add_compare_game(&game_dir);
+            } else {
+                self.open_game(&game_dir);
+            }
+        }
+    }
+
+    // Opening another game would lose any unsaved edits, so check what to do with them first
+    pub fn unsaved_changes_ui(&mut self, ctx : &egui::Context) {
+        let Some(game_dir) = self.pending_open.clone() else {
+            return;
+        };
+
+        Window::new("Unsaved Changes").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(format!("{} edited picture(s) haven't been saved.  Save them before opening {}?", self.unsaved_pics.len(), game_dir.display()));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.save_pics();
+                    self.pending_open = None;
+                    // Anything that couldn't be saved stays unsaved, and asks again
+                    self.open_game(&game_dir);
+                }
+                if ui.button("Discard").clicked() {
+                    self.pending_open = None;
+                    self.reopen_game(&game_dir);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.pending_open = None;
+                }
+            });
+            if let Some(save_status) = &self.save_status {
+                ui.label(save_status);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_games_are_kept_in_order() {
+        let mut recent_games = vec![];
+        for i in 0..MAX_RECENT_GAMES + 2 {
+            add_recent_game(&mut recent_games, &PathBuf::from(format!("game{}", i)));
+        }
+        add_recent_game(&mut recent_games, Path::new("game5"));
+
+        assert_eq!(recent_games.len(), MAX_RECENT_GAMES);
+        assert_eq!(recent_games[0], Path::new("game5"));
+        assert_eq!(recent_games[1], Path::new("game11"));
+        assert_eq!(recent_games.iter().filter(|dir| *dir == Path::new("game5")).count(), 1);
+
+        let path = env::temp_dir().join(format!("agi-pic-viewer-test-{}", std::process::id())).join("recent_games.json");
+        write_recent_games(&path, &recent_games).unwrap();
+        assert_eq!(read_recent_games(&path), recent_games);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert!(read_recent_games(&path).is_empty(), "A missing file is just no recent games");
+    }
+}